@@ -0,0 +1,525 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_smithy_http::body::{BoxBody, Error, SdkBody};
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http::{HeaderMap, HeaderValue};
+use http_body::{Body, SizeHint};
+use pin_project_lite::pin_project;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Values for the `Content-Encoding` header
+pub mod header_value {
+    /// Header value denoting "aws-chunked" encoding
+    pub const AWS_CHUNKED: &str = "aws-chunked";
+
+    /// Header value denoting "gzip" encoding
+    pub const GZIP: &str = "gzip";
+}
+
+/// Names of the headers used with `aws-chunked` bodies
+pub mod header {
+    /// The length of the body once it has been decoded from `aws-chunked`
+    pub const X_AMZ_DECODED_CONTENT_LENGTH: &str = "x-amz-decoded-content-length";
+}
+
+/// The smallest possible gzip stream: a 10 byte header, an empty deflate block, and an 8 byte footer.
+const MIN_GZIP_LENGTH: u64 = 20;
+
+/// Options used when creating a [`CompressedBody`]
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CompressionOptions {
+    min_compression_size_bytes: u64,
+}
+
+impl CompressionOptions {
+    /// Create a new [`CompressionOptions`] that compresses bodies of any size
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bodies with an exact size smaller than `min_compression_size_bytes` are passed through
+    /// uncompressed. Bodies of unknown size are always compressed.
+    pub fn with_min_compression_size_bytes(mut self, min_compression_size_bytes: u64) -> Self {
+        self.min_compression_size_bytes = min_compression_size_bytes;
+        self
+    }
+
+    fn should_compress(&self, size_hint: &SizeHint) -> bool {
+        match size_hint.exact() {
+            Some(size) => size >= self.min_compression_size_bytes,
+            None => true,
+        }
+    }
+}
+
+pin_project! {
+    /// A request body compressed with gzip.
+    ///
+    /// Each frame of the inner body is fed to a gzip encoder and whatever compressed output is
+    /// available is emitted. Because the compressed size can't be known ahead of time,
+    /// [`size_hint`](Body::size_hint) only reports a lower bound; requests using this body must not
+    /// set a `Content-Length` derived from the uncompressed body.
+    ///
+    /// If the inner body is smaller than the configured
+    /// [minimum compression size](CompressionOptions::with_min_compression_size_bytes), it is
+    /// passed through unchanged and [`content_encoding`](CompressedBody::content_encoding) returns
+    /// `None`. Trailers of the inner body are passed through in either case.
+    #[derive(Debug)]
+    pub struct CompressedBody<InnerBody> {
+        #[pin]
+        inner: InnerBody,
+        // `None` when passing through, or once the gzip stream has been finished
+        encoder: Option<GzEncoder<Vec<u8>>>,
+        compressing: bool,
+    }
+}
+
+impl<Inner> CompressedBody<Inner>
+where
+    Inner: Body,
+{
+    /// Wrap the given body in a gzip-compressing body
+    pub fn new(body: Inner, options: CompressionOptions) -> Self {
+        let compressing = options.should_compress(&body.size_hint());
+        Self {
+            inner: body,
+            encoder: if compressing {
+                Some(GzEncoder::new(Vec::new(), Compression::default()))
+            } else {
+                None
+            },
+            compressing,
+        }
+    }
+
+    /// The `Content-Encoding` header value that must be sent with this body, or `None` if
+    /// the body is being passed through uncompressed
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        if self.compressing {
+            Some(header_value::GZIP)
+        } else {
+            None
+        }
+    }
+}
+
+impl CompressedBody<SdkBody> {
+    /// Compress an [`SdkBody`], returning the new body and the `Content-Encoding` to send with it.
+    ///
+    /// If the given body can be retried, so can the returned body. Callbacks added to the returned
+    /// body (e.g. checksum callbacks) will see the compressed bytes.
+    pub fn compress_sdk_body(
+        body: SdkBody,
+        options: CompressionOptions,
+    ) -> (SdkBody, Option<&'static str>) {
+        if !options.should_compress(&body.size_hint()) {
+            return (body, None);
+        }
+        let body = body.map(move |body| {
+            SdkBody::from_dyn(BoxBody::new(CompressedBody::new(body, options.clone())))
+        });
+        (body, Some(header_value::GZIP))
+    }
+}
+
+impl<Inner> Body for CompressedBody<Inner>
+where
+    Inner: Body<Data = Bytes, Error = Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let mut this = self.project();
+        if !*this.compressing {
+            return this.inner.poll_data(cx);
+        }
+
+        loop {
+            let encoder = match this.encoder.as_mut() {
+                Some(encoder) => encoder,
+                None => return Poll::Ready(None),
+            };
+            match this.inner.as_mut().poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    encoder.write_all(&data)?;
+                    let compressed = std::mem::take(encoder.get_mut());
+                    // The encoder buffers internally, so small frames may not produce output yet
+                    if !compressed.is_empty() {
+                        return Poll::Ready(Some(Ok(compressed.into())));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => {
+                    let encoder = this.encoder.take().expect("checked above");
+                    let compressed = encoder.finish()?;
+                    return Poll::Ready(Some(Ok(compressed.into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        if self.compressing {
+            self.encoder.is_none()
+        } else {
+            self.inner.is_end_stream()
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if !self.compressing {
+            return self.inner.size_hint();
+        }
+        let mut size_hint = SizeHint::new();
+        if self.encoder.is_some() {
+            size_hint.set_lower(MIN_GZIP_LENGTH);
+        }
+        size_hint
+    }
+}
+
+pin_project! {
+    /// Verifies that a body decoded from `aws-chunked` is as long as its sender declared.
+    ///
+    /// `aws-chunked` requests declare the length of their decoded body in the
+    /// [`x-amz-decoded-content-length`](header::X_AMZ_DECODED_CONTENT_LENGTH) header. This wraps the
+    /// decoded body and counts its bytes: rather than ending, a body that ends short of the declared
+    /// length yields a [`DecodedLengthMismatch`] error, so that truncated uploads aren't accepted.
+    /// A body that exceeds the declared length yields the error as soon as it does.
+    #[derive(Debug)]
+    pub struct DecodedLengthBody<InnerBody> {
+        #[pin]
+        inner: InnerBody,
+        decoded_content_length: u64,
+        decoded: u64,
+        done: bool,
+    }
+}
+
+impl<Inner> DecodedLengthBody<Inner> {
+    /// Wrap a decoded body that must be `decoded_content_length` bytes long
+    pub fn new(body: Inner, decoded_content_length: u64) -> Self {
+        Self {
+            inner: body,
+            decoded_content_length,
+            decoded: 0,
+            done: false,
+        }
+    }
+
+    /// Wrap a decoded body, verifying it against the `x-amz-decoded-content-length` of `headers`.
+    ///
+    /// Returns `None` if the header is missing or isn't a valid length.
+    pub fn from_headers(body: Inner, headers: &HeaderMap<HeaderValue>) -> Option<Self> {
+        let decoded_content_length = headers
+            .get(header::X_AMZ_DECODED_CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()?;
+        Some(Self::new(body, decoded_content_length))
+    }
+}
+
+impl<Inner> Body for DecodedLengthBody<Inner>
+where
+    Inner: Body<Data = Bytes, Error = Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+        let mismatch = |actual| DecodedLengthMismatch {
+            expected: *this.decoded_content_length,
+            actual,
+        };
+        let data = match this.inner.poll_data(cx) {
+            Poll::Ready(data) => data,
+            Poll::Pending => return Poll::Pending,
+        };
+        match data {
+            Some(Ok(data)) => {
+                *this.decoded += data.len() as u64;
+                if *this.decoded > *this.decoded_content_length {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(mismatch(*this.decoded).into())));
+                }
+                Poll::Ready(Some(Ok(data)))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(err))),
+            None => {
+                *this.done = true;
+                if *this.decoded != *this.decoded_content_length {
+                    return Poll::Ready(Some(Err(mismatch(*this.decoded).into())));
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        // The length is only verified once the inner body has been polled to its end, or as soon
+        // as it exceeds the declared length, after which nothing more is read from it
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Error yielded by a [`DecodedLengthBody`] whose length doesn't match its declared
+/// `x-amz-decoded-content-length`.
+#[derive(Debug)]
+pub struct DecodedLengthMismatch {
+    expected: u64,
+    actual: u64,
+}
+
+impl DecodedLengthMismatch {
+    /// The declared decoded length of the body
+    pub fn expected(&self) -> u64 {
+        self.expected
+    }
+
+    /// The number of decoded bytes received. If the body exceeded its declared length, this is the
+    /// number of bytes received by the time it did.
+    pub fn actual(&self) -> u64 {
+        self.actual
+    }
+}
+
+impl fmt::Display for DecodedLengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the decoded body was {} bytes long, but its declared decoded content length was {} bytes",
+            self.actual, self.expected
+        )
+    }
+}
+
+impl StdError for DecodedLengthMismatch {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        header_value, CompressedBody, CompressionOptions, DecodedLengthBody, DecodedLengthMismatch,
+    };
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::callback::BodyCallback;
+    use bytes::{Bytes, BytesMut};
+    use flate2::read::GzDecoder;
+    use http::{HeaderMap, HeaderValue};
+    use http_body::Body;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn collect<B>(mut body: B) -> (Bytes, Option<HeaderMap<HeaderValue>>)
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: std::fmt::Debug,
+    {
+        let mut output = BytesMut::new();
+        while let Some(data) = body.data().await {
+            output.extend_from_slice(&data.unwrap());
+        }
+        let trailers = body.trailers().await.unwrap();
+        (output.freeze(), trailers)
+    }
+
+    fn gunzip(bytes: &[u8]) -> String {
+        let mut decompressed = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut decompressed)
+            .expect("output is valid gzip");
+        decompressed
+    }
+
+    fn streaming_body(lines: usize) -> SdkBody {
+        let stream = tokio_stream::iter(
+            (0..lines).map(|n| -> Result<String, std::io::Error> { Ok(format!("line {}\n", n)) }),
+        );
+        SdkBody::from(hyper::Body::wrap_stream(stream))
+    }
+
+    #[tokio::test]
+    async fn compressed_body_round_trips() {
+        let body = CompressedBody::new(streaming_body(1000), CompressionOptions::new());
+        assert_eq!(Some(header_value::GZIP), body.content_encoding());
+        assert_eq!(None, body.size_hint().exact());
+
+        let (compressed, trailers) = collect(body).await;
+        let expected: String = (0..1000).map(|n| format!("line {}\n", n)).collect();
+        assert!(compressed.len() < expected.len());
+        assert_eq!(expected, gunzip(&compressed));
+        assert!(trailers.is_none());
+    }
+
+    #[tokio::test]
+    async fn empty_body_produces_valid_gzip() {
+        let body = CompressedBody::new(SdkBody::empty(), CompressionOptions::new());
+        let (compressed, _) = collect(body).await;
+        assert_eq!("", gunzip(&compressed));
+    }
+
+    #[tokio::test]
+    async fn small_bodies_pass_through_uncompressed() {
+        let options = CompressionOptions::new().with_min_compression_size_bytes(1024);
+        let body = CompressedBody::new(SdkBody::from("tiny body"), options);
+        assert_eq!(None, body.content_encoding());
+        assert_eq!(Some(9), body.size_hint().exact());
+
+        let (output, _) = collect(body).await;
+        assert_eq!(&b"tiny body"[..], &output[..]);
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingCallback(Arc<Mutex<Vec<u8>>>);
+
+    impl BodyCallback for RecordingCallback {
+        fn update(&mut self, bytes: &[u8]) -> Result<(), BoxError> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(())
+        }
+
+        fn trailers(&self) -> Result<Option<HeaderMap<HeaderValue>>, BoxError> {
+            let mut trailers = HeaderMap::new();
+            trailers.insert(
+                "x-recorded-length",
+                HeaderValue::from(self.0.lock().unwrap().len()),
+            );
+            Ok(Some(trailers))
+        }
+
+        fn make_new(&self) -> Box<dyn BodyCallback> {
+            Box::new(RecordingCallback::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn callbacks_see_compressed_bytes() {
+        let (mut body, content_encoding) =
+            CompressedBody::compress_sdk_body(SdkBody::from("x".repeat(4096)), Default::default());
+        assert_eq!(Some(header_value::GZIP), content_encoding);
+        let callback = RecordingCallback::default();
+        body.with_callback(Box::new(callback.clone()));
+
+        let (compressed, trailers) = collect(body).await;
+        assert_eq!(&compressed[..], &callback.0.lock().unwrap()[..]);
+        assert_eq!(
+            compressed.len().to_string(),
+            trailers.unwrap()["x-recorded-length"]
+        );
+        assert_eq!("x".repeat(4096), gunzip(&compressed));
+    }
+
+    #[tokio::test]
+    async fn compressed_sdk_body_is_retryable() {
+        let (body, _) =
+            CompressedBody::compress_sdk_body(SdkBody::from("retry me"), Default::default());
+        let retry = body.try_clone().expect("in-memory bodies are retryable");
+
+        let (first, _) = collect(body).await;
+        let (second, _) = collect(retry).await;
+        assert_eq!(first, second);
+        assert_eq!("retry me", gunzip(&second));
+    }
+
+    #[test]
+    fn streaming_bodies_are_not_made_retryable() {
+        let (body, _) =
+            CompressedBody::compress_sdk_body(streaming_body(1), CompressionOptions::new());
+        assert!(body.try_clone().is_none());
+    }
+
+    async fn decoded_length_error<B>(mut body: B) -> Option<DecodedLengthMismatch>
+    where
+        B: Body<Data = Bytes, Error = BoxError> + Unpin,
+    {
+        while let Some(data) = body.data().await {
+            if let Err(err) = data {
+                return Some(*err.downcast::<DecodedLengthMismatch>().unwrap());
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn truncated_decoded_body_is_an_error() {
+        let body = DecodedLengthBody::new(streaming_body(2), 20);
+        let err = decoded_length_error(body)
+            .await
+            .expect("the body is shorter than declared");
+        assert_eq!(20, err.expected());
+        assert_eq!(14, err.actual());
+        assert_eq!(
+            "the decoded body was 14 bytes long, but its declared decoded content length was 20 bytes",
+            err.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn decoded_body_longer_than_declared_is_an_error() {
+        let body = DecodedLengthBody::new(streaming_body(2), 10);
+        let err = decoded_length_error(body)
+            .await
+            .expect("the body is longer than declared");
+        assert_eq!(14, err.actual());
+    }
+
+    #[tokio::test]
+    async fn decoded_body_ends_after_length_error() {
+        let mut body = DecodedLengthBody::new(streaming_body(2), 5);
+        assert!(body.data().await.unwrap().is_err());
+        assert!(body.is_end_stream());
+        assert!(body.data().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn decoded_body_of_declared_length() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-decoded-content-length", HeaderValue::from(14));
+        let body = DecodedLengthBody::from_headers(streaming_body(2), &headers).unwrap();
+        assert!(decoded_length_error(body).await.is_none());
+        assert!(DecodedLengthBody::from_headers(streaming_body(2), &HeaderMap::new()).is_none());
+    }
+}
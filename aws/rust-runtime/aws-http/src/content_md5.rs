@@ -0,0 +1,141 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_smithy_http::middleware::MapRequest;
+use aws_smithy_http::operation::Request;
+use http::header::HeaderName;
+use http::HeaderValue;
+use md5::{Digest, Md5};
+use std::error::Error;
+use std::fmt;
+
+/// Operation property requesting that [`ContentMd5Stage`] set the `Content-MD5` header.
+///
+/// Operations such as S3's `DeleteObjects` require the header; insert this into their property
+/// bag to have it computed from the request body.
+#[non_exhaustive]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ContentMd5Required;
+
+impl ContentMd5Required {
+    /// Creates a new `ContentMd5Required`
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Content-MD5 Middleware
+///
+/// When the property bag contains [`ContentMd5Required`], this middleware computes the MD5 digest of
+/// the request body and sets it, base64-encoded, as the `Content-MD5` header. The body must be
+/// in memory: streaming bodies can't be hashed without consuming them, so they result in a
+/// [`ContentMd5StageError`].
+#[non_exhaustive]
+#[derive(Default, Debug, Clone)]
+pub struct ContentMd5Stage;
+
+impl ContentMd5Stage {
+    /// Creates a new `ContentMd5Stage`
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Failures that can occur in the [`ContentMd5Stage`]
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ContentMd5StageError {
+    /// The request body is streaming, so its MD5 digest can't be computed up front.
+    StreamingBody,
+}
+
+impl Error for ContentMd5StageError {}
+
+impl fmt::Display for ContentMd5StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StreamingBody => write!(
+                f,
+                "Content-MD5 can't be computed for a streaming request body. \
+                 The body must be fully buffered in memory."
+            ),
+        }
+    }
+}
+
+impl MapRequest for ContentMd5Stage {
+    type Error = ContentMd5StageError;
+
+    fn apply(&self, request: Request) -> Result<Request, Self::Error> {
+        request.augment(|mut req, conf| {
+            if conf.get::<ContentMd5Required>().is_none() {
+                return Ok(req);
+            }
+            let body = req
+                .body()
+                .bytes()
+                .ok_or(ContentMd5StageError::StreamingBody)?;
+            let checksum = aws_smithy_types::base64::encode(Md5::digest(body));
+            req.headers_mut().insert(
+                HeaderName::from_static("content-md5"),
+                HeaderValue::try_from(checksum).expect("base64 is a valid header value"),
+            );
+            Ok(req)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::content_md5::{ContentMd5Required, ContentMd5Stage, ContentMd5StageError};
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::middleware::MapRequest;
+    use aws_smithy_http::operation;
+
+    fn request(body: SdkBody, required: bool) -> operation::Request {
+        let mut req = operation::Request::new(http::Request::new(body));
+        if required {
+            req.properties_mut().insert(ContentMd5Required::new());
+        }
+        req
+    }
+
+    #[test]
+    fn sets_content_md5() {
+        let req = request(
+            SdkBody::from("The quick brown fox jumps over the lazy dog"),
+            true,
+        );
+        let req = ContentMd5Stage::new()
+            .apply(req)
+            .expect("body is in memory");
+        // MD5 9e107d9d372bb6826bd81d3542a419d6, base64-encoded
+        assert_eq!(
+            "nhB9nTcrtoJr2B01QqQZ1g==",
+            req.http().headers()["content-md5"]
+        );
+    }
+
+    #[test]
+    fn only_when_required() {
+        let req = request(SdkBody::from("hello"), false);
+        let req = ContentMd5Stage::new().apply(req).expect("nothing to do");
+        assert!(req.http().headers().get("content-md5").is_none());
+    }
+
+    #[test]
+    fn streaming_body_is_an_error() {
+        let body = SdkBody::from(hyper::Body::wrap_stream(tokio_stream::iter(vec![Ok::<
+            _,
+            std::io::Error,
+        >(
+            "hello",
+        )])));
+        let err = ContentMd5Stage::new()
+            .apply(request(body, true))
+            .expect_err("streaming bodies can't be hashed");
+        assert!(matches!(err, ContentMd5StageError::StreamingBody));
+    }
+}
@@ -16,6 +16,12 @@
 /// Credentials middleware
 pub mod auth;
 
+/// Bodies and header values for request `Content-Encoding`s
+pub mod content_encoding;
+
+/// Content-MD5 middleware
+pub mod content_md5;
+
 /// Recursion Detection middleware
 pub mod recursion_detection;
 
@@ -17,6 +17,9 @@ use std::borrow::Cow;
 /// variables to detect if the request is being invoked in a lambda function. If it is, the `X-Amzn-Trace-Id` header
 /// will be set. This enables downstream services to prevent accidentally infinitely recursive invocations spawned
 /// from lambda.
+///
+/// This middleware is part of the default middleware stack, and applies to every operation unless
+/// [`DisableRecursionDetection`] is present in the operation's property bag.
 #[non_exhaustive]
 #[derive(Default, Debug, Clone)]
 pub struct RecursionDetectionStage {
@@ -30,12 +33,29 @@ impl RecursionDetectionStage {
     }
 }
 
+/// Operation property that disables the [`RecursionDetectionStage`] for an operation.
+///
+/// Use this for operations that are never invoked from Lambda, or for which the `X-Amzn-Trace-Id`
+/// header causes issues. The stage stays in the middleware stack, but leaves requests untouched.
+#[non_exhaustive]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct DisableRecursionDetection;
+
+impl DisableRecursionDetection {
+    /// Creates a new `DisableRecursionDetection`
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 impl MapRequest for RecursionDetectionStage {
     type Error = std::convert::Infallible;
 
     fn apply(&self, request: Request) -> Result<Request, Self::Error> {
-        request.augment(|mut req, _conf| {
-            augument_request(&mut req, &self.env);
+        request.augment(|mut req, conf| {
+            if conf.get::<DisableRecursionDetection>().is_none() {
+                augument_request(&mut req, &self.env);
+            }
             Ok(req)
         })
     }
@@ -71,7 +91,9 @@ fn encode_header(value: &[u8]) -> HeaderValue {
 
 #[cfg(test)]
 mod test {
-    use crate::recursion_detection::{encode_header, RecursionDetectionStage};
+    use crate::recursion_detection::{
+        encode_header, DisableRecursionDetection, RecursionDetectionStage,
+    };
     use aws_smithy_http::body::SdkBody;
     use aws_smithy_http::middleware::MapRequest;
     use aws_smithy_http::operation;
@@ -100,6 +122,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn disabled_per_operation() {
+        let env = Env::from_slice(&[
+            ("AWS_LAMBDA_FUNCTION_NAME", "some-function"),
+            ("_X_AMZN_TRACE_ID", "traceid"),
+        ]);
+        let stage = RecursionDetectionStage { env };
+        let request = || operation::Request::new(http::Request::new(SdkBody::empty()));
+
+        let enabled = stage.apply(request()).expect("stage must succeed");
+        assert_eq!("traceid", enabled.http().headers()["x-amzn-trace-id"]);
+
+        let mut disabled = request();
+        disabled
+            .properties_mut()
+            .insert(DisableRecursionDetection::new());
+        let disabled = stage.apply(disabled).expect("stage must succeed");
+        assert!(disabled.http().headers().get("x-amzn-trace-id").is_none());
+    }
+
     #[test]
     fn run_tests() {
         let test_cases: Vec<TestCase> =
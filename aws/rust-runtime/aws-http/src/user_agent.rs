@@ -5,7 +5,7 @@
 
 use aws_smithy_http::middleware::MapRequest;
 use aws_smithy_http::operation::Request;
-use aws_types::app_name::AppName;
+use aws_types::app_name::{AppName, InvalidAppName};
 use aws_types::build_metadata::{OsFamily, BUILD_METADATA};
 use aws_types::os_shim_internal::Env;
 use http::header::{HeaderName, InvalidHeaderValue, USER_AGENT};
@@ -513,6 +513,31 @@ impl fmt::Display for ExecEnvMetadata {
     }
 }
 
+/// Application identifier for a single request
+///
+/// When inserted into the [`PropertyBag`](aws_smithy_http::operation::Request::properties) of an
+/// operation, [`UserAgentStage`] appends it to the `x-amz-user-agent` header as `app/<app id>`,
+/// after the [app name](AwsUserAgent::with_app_name) the client was configured with, if any. This
+/// lets multi-tenant applications attribute individual requests to a tenant. The legacy
+/// `User-Agent` header never includes an app id.
+#[derive(Clone, Debug)]
+pub struct UserAgentAppId(AppName);
+
+impl UserAgentAppId {
+    /// Creates a new app id.
+    ///
+    /// The app id must meet the same character requirements as an [`AppName`].
+    pub fn new(app_id: impl Into<Cow<'static, str>>) -> Result<Self, InvalidAppName> {
+        AppName::new(app_id).map(Self)
+    }
+}
+
+impl From<AppName> for UserAgentAppId {
+    fn from(app_name: AppName) -> Self {
+        Self(app_name)
+    }
+}
+
 /// User agent middleware
 #[non_exhaustive]
 #[derive(Default, Clone, Debug)]
@@ -565,11 +590,16 @@ impl MapRequest for UserAgentStage {
             let ua = conf
                 .get::<AwsUserAgent>()
                 .ok_or(UserAgentStageError::UserAgentMissing)?;
+            let mut aws_ua_header = ua.aws_ua_header();
+            if let Some(app_id) = conf.get::<UserAgentAppId>() {
+                aws_ua_header.push_str(" app/");
+                aws_ua_header.push_str(app_id.0.as_ref());
+            }
             req.headers_mut()
                 .append(USER_AGENT, HeaderValue::try_from(ua.ua_header())?);
             req.headers_mut().append(
                 X_AMZ_USER_AGENT.clone(),
-                HeaderValue::try_from(ua.aws_ua_header())?,
+                HeaderValue::try_from(aws_ua_header)?,
             );
 
             Ok(req)
@@ -581,7 +611,7 @@ impl MapRequest for UserAgentStage {
 mod test {
     use crate::user_agent::{
         AdditionalMetadata, ApiMetadata, AwsUserAgent, ConfigMetadata, FrameworkMetadata,
-        UserAgentStage,
+        UserAgentAppId, UserAgentStage,
     };
     use crate::user_agent::{FeatureMetadata, X_AMZ_USER_AGENT};
     use aws_smithy_http::body::SdkBody;
@@ -756,6 +786,33 @@ mod test {
             .get(&*X_AMZ_USER_AGENT)
             .expect("UA header should be set");
     }
+
+    #[test]
+    fn ua_stage_appends_app_id_of_request() {
+        let api_metadata = ApiMetadata {
+            service_id: "dynamodb".into(),
+            version: "123",
+        };
+        let mut ua = AwsUserAgent::new_from_environment(Env::from_slice(&[]), api_metadata)
+            .with_app_name(AppName::new("my_app").unwrap());
+        make_deterministic(&mut ua);
+        let mut req = operation::Request::new(http::Request::new(SdkBody::from("some body")));
+        req.properties_mut().insert(ua);
+        req.properties_mut()
+            .insert(UserAgentAppId::new("tenant-1").unwrap());
+
+        let req = UserAgentStage::new()
+            .apply(req)
+            .expect("setting user agent should succeed");
+        assert_eq!(
+            "aws-sdk-rust/0.1 api/dynamodb/123 os/macos/1.15 lang/rust/1.50.0 app/my_app app/tenant-1",
+            req.http().headers()[&*X_AMZ_USER_AGENT]
+        );
+        assert_eq!(
+            "aws-sdk-rust/0.1 os/macos/1.15 lang/rust/1.50.0",
+            req.http().headers()[USER_AGENT]
+        );
+    }
 }
 
 /*
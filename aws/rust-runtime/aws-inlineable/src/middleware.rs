@@ -11,17 +11,20 @@ use aws_endpoint::AwsEndpointStage;
 use aws_http::auth::CredentialsStage;
 use aws_http::recursion_detection::RecursionDetectionStage;
 use aws_http::user_agent::UserAgentStage;
-use aws_sig_auth::middleware::SigV4SigningStage;
-use aws_sig_auth::signer::SigV4Signer;
+use aws_sig_auth::auth_scheme::AuthSchemeStage;
 use aws_smithy_http_tower::map_request::{AsyncMapRequestLayer, MapRequestLayer};
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use http::StatusCode;
 use std::fmt::Debug;
+use std::time::{Duration, SystemTime};
 use tower::layer::util::{Identity, Stack};
 use tower::ServiceBuilder;
 
 type DefaultMiddlewareStack = Stack<
     MapRequestLayer<RecursionDetectionStage>,
     Stack<
-        MapRequestLayer<SigV4SigningStage>,
+        AsyncMapRequestLayer<AuthSchemeStage>,
         Stack<
             AsyncMapRequestLayer<CredentialsStage>,
             Stack<
@@ -36,7 +39,8 @@ type DefaultMiddlewareStack = Stack<
 ///
 /// This implements the middleware stack for this service. It will:
 /// 1. Load credentials asynchronously into the property bag
-/// 2. Sign the request with SigV4
+/// 2. Sign the request with the [auth scheme](aws_sig_auth::auth_scheme) selected for the
+///    operation, SigV4 by default
 /// 3. Resolve an Endpoint for the request
 /// 4. Add a user agent to the request
 #[derive(Debug, Default, Clone)]
@@ -55,7 +59,7 @@ impl DefaultMiddleware {
 // define the middleware stack in a non-generic location to reduce code bloat.
 fn base() -> ServiceBuilder<DefaultMiddlewareStack> {
     let credential_provider = AsyncMapRequestLayer::for_mapper(CredentialsStage::new());
-    let signer = MapRequestLayer::for_mapper(SigV4SigningStage::new(SigV4Signer::new()));
+    let signer = AsyncMapRequestLayer::for_mapper(AuthSchemeStage::new());
     let endpoint_resolver = MapRequestLayer::for_mapper(AwsEndpointStage);
     let user_agent = MapRequestLayer::for_mapper(UserAgentStage::new());
     let recursion_detection = MapRequestLayer::for_mapper(RecursionDetectionStage::new());
@@ -63,7 +67,7 @@ fn base() -> ServiceBuilder<DefaultMiddlewareStack> {
     // 1. Resolve an endpoint
     // 2. Add a user agent
     // 3. Acquire credentials
-    // 4. Sign with credentials
+    // 4. Sign with the auth scheme selected for the operation
     // (5. Dispatch over the wire)
     ServiceBuilder::new()
         .layer(endpoint_resolver)
@@ -80,3 +84,105 @@ impl<S> tower::Layer<S> for DefaultMiddleware {
         base().service(inner)
     }
 }
+
+/// Returns the backoff that the server suggested in a response, if any.
+///
+/// The `x-amz-retry-after` header, in milliseconds, takes precedence. Otherwise, the `Retry-After`
+/// header of a throttled (`429 Too Many Requests`) or `503 Service Unavailable` response is used,
+/// in either of its forms:
+/// - delta-seconds (e.g. `Retry-After: 120`);
+/// - an HTTP date (e.g. `Retry-After: Wed, 21 Oct 2015 07:28:00 GMT`), relative to `now`. A date
+///   in the past suggests retrying immediately.
+///
+/// Header values that can't be parsed are ignored.
+pub fn retry_after<B>(response: &http::Response<B>, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    if let Some(millis) = header("x-amz-retry-after").and_then(|value| value.parse::<u64>().ok()) {
+        return Some(Duration::from_millis(millis));
+    }
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+    let value = header("retry-after")?;
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = SystemTime::try_from(DateTime::from_str(value, Format::HttpDate).ok()?).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::retry_after;
+    use http::StatusCode;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn response(status: StatusCode, headers: &[(&str, &str)]) -> http::Response<()> {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    // Wed, 21 Oct 2015 07:28:00 GMT
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1445412480)
+    }
+
+    #[test]
+    fn delta_seconds() {
+        let throttled = response(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "120")]);
+        assert_eq!(
+            Some(Duration::from_secs(120)),
+            retry_after(&throttled, now())
+        );
+    }
+
+    #[test]
+    fn http_date() {
+        let unavailable = response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &[("retry-after", "Wed, 21 Oct 2015 07:30:30 GMT")],
+        );
+        assert_eq!(
+            Some(Duration::from_secs(150)),
+            retry_after(&unavailable, now())
+        );
+
+        let past = response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &[("retry-after", "Wed, 21 Oct 2015 07:00:00 GMT")],
+        );
+        assert_eq!(Some(Duration::ZERO), retry_after(&past, now()));
+    }
+
+    #[test]
+    fn x_amz_retry_after_takes_precedence() {
+        let throttled = response(
+            StatusCode::TOO_MANY_REQUESTS,
+            &[("retry-after", "120"), ("x-amz-retry-after", "1500")],
+        );
+        assert_eq!(
+            Some(Duration::from_millis(1500)),
+            retry_after(&throttled, now())
+        );
+    }
+
+    #[test]
+    fn ignored_unless_throttled() {
+        let bad_request = response(StatusCode::BAD_REQUEST, &[("retry-after", "120")]);
+        assert_eq!(None, retry_after(&bad_request, now()));
+        let invalid = response(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "soon")]);
+        assert_eq!(None, retry_after(&invalid, now()));
+    }
+}
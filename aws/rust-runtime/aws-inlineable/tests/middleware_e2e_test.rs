@@ -18,14 +18,20 @@ use aws_endpoint::partition::endpoint::{Protocol, SignatureVersion};
 use aws_endpoint::set_endpoint_resolver;
 use aws_http::retry::AwsErrorRetryPolicy;
 use aws_http::user_agent::AwsUserAgent;
+use aws_sig_auth::auth_scheme::{
+    set_auth_scheme, set_auth_scheme_registry, AuthSchemeRegistry, BearerTokenAuthScheme,
+    HTTP_BEARER_AUTH_SCHEME_ID,
+};
+use aws_sig_auth::middleware::SigningStageError;
 use aws_sig_auth::signer::OperationSigningConfig;
 use inlineable_aws::middleware::DefaultMiddleware;
 
-use aws_smithy_client::test_connection::TestConnection;
+use aws_smithy_client::test_connection::{capture_request, TestConnection};
 use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::operation;
 use aws_smithy_http::operation::Operation;
 use aws_smithy_http::response::ParseHttpResponse;
+use aws_smithy_http::result::SdkError;
 
 use aws_smithy_types::retry::{ErrorKind, ProvideErrorKind};
 use aws_types::credentials::SharedCredentialsProvider;
@@ -144,3 +150,49 @@ async fn e2e_test() {
 
     conn.assert_requests_match(&[]);
 }
+
+#[tokio::test]
+async fn bearer_token_e2e_test() {
+    let mut operation = test_operation();
+    {
+        let mut properties = operation.properties_mut();
+        set_auth_scheme_registry(
+            &mut properties,
+            AuthSchemeRegistry::new().with_scheme(BearerTokenAuthScheme::from_static("token")),
+        );
+        set_auth_scheme(&mut properties, HTTP_BEARER_AUTH_SCHEME_ID);
+    }
+    let (conn, request) = capture_request(None);
+    let client = Client::new(conn);
+    let resp = client.call(operation).await;
+    assert_eq!(resp.expect("successful operation"), "Hello!");
+
+    let request = request.expect_request();
+    assert_eq!("Bearer token", request.headers()[AUTHORIZATION]);
+    assert!(request.headers().get("x-amz-date").is_none());
+}
+
+#[tokio::test]
+async fn missing_credentials_e2e_test() {
+    let mut operation = test_operation();
+    operation
+        .properties_mut()
+        .remove::<SharedCredentialsProvider>();
+    let (conn, _request) = capture_request(None);
+    let client = Client::new(conn);
+    let err = client
+        .call(operation)
+        .await
+        .expect_err("there are no credentials to sign with");
+
+    match err {
+        SdkError::ConstructionFailure(err) => {
+            assert!(matches!(
+                err.downcast_ref::<SigningStageError>(),
+                Some(SigningStageError::MissingCredentials)
+            ));
+            assert_eq!("No credentials in the property bag", err.to_string());
+        }
+        other => panic!("expected a construction failure, got {:?}", other),
+    }
+}
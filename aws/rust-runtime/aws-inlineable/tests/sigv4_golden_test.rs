@@ -0,0 +1,362 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Wire-compatibility tests for SigV4 signatures produced by the default middleware stack.
+//!
+//! Each vector is dispatched through [`DefaultMiddleware`] with fixed credentials and a fixed
+//! signing time. The canonical request (captured from the signer's `trace` output) and the
+//! resulting `Authorization` header are compared against the goldens checked in under
+//! `test-data/sigv4-goldens`. When a change to the signatures is intentional, regenerate the
+//! goldens with:
+//!
+//! ```text
+//! UPDATE_GOLDENS=1 cargo test -p inlineable-aws --test sigv4_golden_test
+//! ```
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::AUTHORIZATION;
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+use aws_endpoint::partition::endpoint::{Protocol, SignatureVersion};
+use aws_endpoint::set_endpoint_resolver;
+use aws_http::retry::AwsErrorRetryPolicy;
+use aws_http::user_agent::AwsUserAgent;
+use aws_sig_auth::signer::{OperationSigningConfig, SignableBody};
+use inlineable_aws::middleware::DefaultMiddleware;
+
+use aws_smithy_client::test_connection::capture_request;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::operation;
+use aws_smithy_http::operation::Operation;
+use aws_smithy_http::response::ParseHttpResponse;
+
+use aws_smithy_types::retry::{ErrorKind, ProvideErrorKind};
+use aws_types::credentials::SharedCredentialsProvider;
+use aws_types::region::Region;
+use aws_types::Credentials;
+use aws_types::SigningService;
+
+type Client<C> = aws_smithy_client::Client<C, DefaultMiddleware>;
+
+#[derive(Clone)]
+struct TestOperationParser;
+
+#[derive(Debug)]
+struct OperationError;
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for OperationError {}
+
+impl ProvideErrorKind for OperationError {
+    fn retryable_error_kind(&self) -> Option<ErrorKind> {
+        None
+    }
+
+    fn code(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl ParseHttpResponse for TestOperationParser {
+    type Output = Result<(), OperationError>;
+
+    fn parse_unloaded(&self, _response: &mut operation::Response) -> Option<Self::Output> {
+        Some(Ok(()))
+    }
+
+    fn parse_loaded(&self, _response: &http::Response<Bytes>) -> Self::Output {
+        Ok(())
+    }
+}
+
+/// A single signing scenario
+struct Vector {
+    name: &'static str,
+    method: &'static str,
+    path_and_query: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+    body: &'static str,
+    session_token: Option<&'static str>,
+    content_sha256_header: bool,
+    unsigned_payload: bool,
+}
+
+impl Vector {
+    const fn new(name: &'static str, method: &'static str, path_and_query: &'static str) -> Self {
+        Vector {
+            name,
+            method,
+            path_and_query,
+            headers: &[],
+            body: "",
+            session_token: None,
+            content_sha256_header: false,
+            unsigned_payload: false,
+        }
+    }
+
+    fn operation(&self) -> Operation<TestOperationParser, AwsErrorRetryPolicy> {
+        let mut request = http::Request::builder().method(self.method).uri(format!(
+            "https://test-service.test-region.amazonaws.com{}",
+            self.path_and_query
+        ));
+        for (name, value) in self.headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(SdkBody::from(self.body)).unwrap();
+
+        let mut signing_config = OperationSigningConfig::default_config();
+        signing_config.signing_options.content_sha256_header = self.content_sha256_header;
+        let session_token = self.session_token.map(|token| token.to_string());
+        let unsigned_payload = self.unsigned_payload;
+
+        let req = operation::Request::new(request)
+            .augment(move |req, conf| {
+                set_endpoint_resolver(
+                    conf,
+                    Arc::new(aws_endpoint::partition::endpoint::Metadata {
+                        uri_template: "test-service.{region}.amazonaws.com",
+                        protocol: Protocol::Https,
+                        credential_scope: Default::default(),
+                        signature_versions: SignatureVersion::V4,
+                    }),
+                );
+                aws_http::auth::set_provider(
+                    conf,
+                    SharedCredentialsProvider::new(Credentials::new(
+                        "access_key",
+                        "secret_key",
+                        session_token,
+                        None,
+                        "test",
+                    )),
+                );
+                conf.insert(Region::new("test-region"));
+                conf.insert(signing_config);
+                conf.insert(SigningService::from_static("test-service-signing"));
+                conf.insert(UNIX_EPOCH + Duration::from_secs(1613414417));
+                conf.insert(AwsUserAgent::for_tests());
+                if unsigned_payload {
+                    conf.insert(SignableBody::UnsignedPayload);
+                }
+                Result::<_, Infallible>::Ok(req)
+            })
+            .unwrap();
+        Operation::new(req, TestOperationParser).with_retry_policy(AwsErrorRetryPolicy::new())
+    }
+}
+
+const VECTORS: &[Vector] = &[
+    Vector::new("get-root", "GET", "/"),
+    Vector::new(
+        "tricky-uri-characters",
+        "GET",
+        "/a%20path/with~tilde/%E2%9C%93/%2Fslash/",
+    ),
+    Vector::new(
+        "repeated-query-params",
+        "GET",
+        "/?b=2&a=1&a=0&empty=&encoded=%20space",
+    ),
+    Vector {
+        headers: &[("x-custom-header", "  multiple   spaces  ")],
+        body: "signed payload",
+        content_sha256_header: true,
+        ..Vector::new("signed-payload", "PUT", "/object")
+    },
+    Vector {
+        body: "unsigned payload",
+        content_sha256_header: true,
+        unsigned_payload: true,
+        ..Vector::new("unsigned-payload", "PUT", "/object")
+    },
+    Vector {
+        session_token: Some("session_token"),
+        ..Vector::new("session-token", "POST", "/")
+    },
+];
+
+/// Collects the canonical requests that `aws-sigv4` logs at `trace` level while signing
+#[derive(Clone, Default)]
+struct CanonicalRequestCapture(Arc<Mutex<Vec<String>>>);
+
+impl<S: Subscriber> Layer<S> for CanonicalRequestCapture {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        struct Visitor<'a>(&'a Mutex<Vec<String>>);
+        impl Visit for Visitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                if field.name() == "canonical_request" {
+                    self.0.lock().unwrap().push(format!("{:?}", value));
+                }
+            }
+        }
+        event.record(&mut Visitor(&self.0));
+    }
+}
+
+/// The components of a canonical request, in the order they appear
+const COMPONENTS: &[&str] = &[
+    "HTTP method",
+    "canonical URI",
+    "canonical query string",
+    "canonical headers",
+    "signed headers",
+    "payload hash",
+];
+
+/// Splits a canonical request into the named components listed in [`COMPONENTS`]
+fn canonical_request_components(canonical_request: &str) -> Vec<String> {
+    let mut lines = canonical_request.split('\n');
+    let mut components: Vec<String> = lines.by_ref().take(3).map(String::from).collect();
+    let headers: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+    components.push(headers.join("\n"));
+    components.extend(lines.map(String::from));
+    components
+}
+
+struct Golden {
+    canonical_request: String,
+    authorization: String,
+}
+
+impl Golden {
+    fn path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/sigv4-goldens")
+            .join(format!("{}.golden", name))
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "# canonical request\n{}\n# authorization\n{}\n",
+            self.canonical_request, self.authorization
+        )
+    }
+
+    fn parse(contents: &str) -> Golden {
+        let contents = contents
+            .strip_prefix("# canonical request\n")
+            .expect("golden starts with the canonical request section");
+        let (canonical_request, authorization) = contents
+            .split_once("\n# authorization\n")
+            .expect("golden contains an authorization section");
+        Golden {
+            canonical_request: canonical_request.to_string(),
+            authorization: authorization.trim_end().to_string(),
+        }
+    }
+
+    /// Panics with the name of the first component that diverges from `self`
+    fn assert_matches(&self, name: &str, actual: &Golden) {
+        let expected_components = canonical_request_components(&self.canonical_request);
+        let actual_components = canonical_request_components(&actual.canonical_request);
+        for (index, component) in COMPONENTS.iter().enumerate() {
+            assert_eq!(
+                expected_components.get(index),
+                actual_components.get(index),
+                "vector `{}`: the {} of the canonical request diverged from the golden",
+                name,
+                component
+            );
+        }
+        assert_eq!(
+            self.canonical_request, actual.canonical_request,
+            "vector `{}`: the canonical request diverged from the golden",
+            name
+        );
+        assert_eq!(
+            self.authorization, actual.authorization,
+            "vector `{}`: the canonical request matched but the Authorization header diverged \
+             (credential scope, signing time, or signature calculation changed)",
+            name
+        );
+    }
+}
+
+async fn sign(vector: &Vector) -> Golden {
+    let capture = CanonicalRequestCapture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (conn, request) = capture_request(None);
+    let client = Client::new(conn);
+    client
+        .call(vector.operation())
+        .await
+        .expect("operation succeeds");
+
+    let request = request.expect_request();
+    let authorization = request
+        .headers()
+        .get(AUTHORIZATION)
+        .expect("request is signed")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let mut canonical_requests = capture.0.lock().unwrap();
+    assert_eq!(
+        1,
+        canonical_requests.len(),
+        "vector `{}`: expected exactly one signed request",
+        vector.name
+    );
+    Golden {
+        canonical_request: canonical_requests.remove(0),
+        authorization,
+    }
+}
+
+#[tokio::test]
+async fn sigv4_signatures_match_goldens() {
+    let update_goldens = std::env::var_os("UPDATE_GOLDENS").is_some();
+    for vector in VECTORS {
+        let actual = sign(vector).await;
+        let path = Golden::path(vector.name);
+        if update_goldens {
+            std::fs::write(&path, actual.render()).expect("failed to write golden");
+            continue;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "missing golden {:?}; rerun with UPDATE_GOLDENS=1 to create it",
+                path
+            )
+        });
+        Golden::parse(&expected).assert_matches(vector.name, &actual);
+    }
+}
+
+#[test]
+fn canonical_request_components_are_split_correctly() {
+    let components = canonical_request_components(
+        "GET\n/\na=1\nhost:example.com\nx-amz-date:20210215T184017Z\n\nhost;x-amz-date\nUNSIGNED-PAYLOAD",
+    );
+    assert_eq!(
+        vec![
+            "GET",
+            "/",
+            "a=1",
+            "host:example.com\nx-amz-date:20210215T184017Z",
+            "host;x-amz-date",
+            "UNSIGNED-PAYLOAD"
+        ],
+        components
+    );
+}
@@ -0,0 +1,547 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Pluggable schemes for authenticating requests, such as SigV4 or bearer tokens.
+//!
+//! An [`AuthScheme`] resolves an [`Identity`] and then uses it to sign a request. Auth schemes are
+//! registered by [`AuthSchemeId`] in an [`AuthSchemeRegistry`], which is usually configured on the
+//! service config and placed into the property bag of each operation with
+//! [`set_auth_scheme_registry`]. The scheme used for a given request is selected by inserting its
+//! [`AuthSchemeId`] into the property bag with [`set_auth_scheme`].
+//!
+//! [`AuthSchemeStage`] signs each request with the scheme selected for it. When no scheme is
+//! selected, [`SIGV4_SCHEME_ID`] is used, and [`SigV4AuthScheme`] signs the request exactly as
+//! [`SigV4SigningStage`](crate::middleware::SigV4SigningStage) does, failing with the same
+//! [`SigningStageError`](crate::middleware::SigningStageError)s.
+//!
+//! [`BearerTokenAuthScheme`] is provided for services that authenticate with OAuth bearer tokens.
+
+use crate::middleware::sign_request;
+use crate::signer::SigV4Signer;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::middleware::AsyncMapRequest;
+use aws_smithy_http::operation::Request;
+use aws_smithy_http::property_bag::PropertyBag;
+use aws_types::Credentials;
+use http::header::{HeaderValue, AUTHORIZATION};
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Identifier of an auth scheme, e.g. `aws.auth#sigv4`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct AuthSchemeId(&'static str);
+
+impl AuthSchemeId {
+    /// Create a new auth scheme ID
+    pub const fn new(id: &'static str) -> Self {
+        Self(id)
+    }
+
+    /// The string form of this ID
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for AuthSchemeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// ID of the SigV4 auth scheme. This is the scheme used when no other scheme is selected.
+pub const SIGV4_SCHEME_ID: AuthSchemeId = AuthSchemeId::new("aws.auth#sigv4");
+
+/// ID of the HTTP bearer token auth scheme
+pub const HTTP_BEARER_AUTH_SCHEME_ID: AuthSchemeId = AuthSchemeId::new("smithy.api#httpBearerAuth");
+
+/// Selects the auth scheme used to sign this request
+pub fn set_auth_scheme(bag: &mut PropertyBag, scheme_id: AuthSchemeId) {
+    bag.insert(scheme_id);
+}
+
+/// Returns the auth scheme selected for a request, defaulting to [`SIGV4_SCHEME_ID`]
+pub fn selected_auth_scheme(bag: &PropertyBag) -> AuthSchemeId {
+    bag.get::<AuthSchemeId>()
+        .copied()
+        .unwrap_or(SIGV4_SCHEME_ID)
+}
+
+/// Sets the auth scheme registry in the given property bag
+pub fn set_auth_scheme_registry(bag: &mut PropertyBag, registry: AuthSchemeRegistry) {
+    bag.insert(registry);
+}
+
+/// Something that a request can be signed with, such as a token or credentials
+///
+/// The contents of an identity are specific to the [`AuthScheme`] that resolved it.
+#[derive(Clone)]
+pub struct Identity {
+    data: Arc<dyn Any + Send + Sync>,
+}
+
+impl Identity {
+    /// Create a new identity wrapping `data`
+    pub fn new(data: impl Any + Send + Sync) -> Self {
+        Self {
+            data: Arc::new(data),
+        }
+    }
+
+    /// Returns the wrapped data if it is a `T`
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Identities are secrets, so their contents are never printed
+        f.debug_struct("Identity").finish()
+    }
+}
+
+/// A scheme used to authenticate requests
+pub trait AuthScheme: Send + Sync + fmt::Debug {
+    /// The ID this scheme is registered and selected by
+    fn scheme_id(&self) -> AuthSchemeId;
+
+    /// Resolve the identity to sign the request with
+    ///
+    /// Anything needed from the property bag must be read before returning the future.
+    fn resolve_identity(&self, properties: &PropertyBag) -> BoxFuture<Result<Identity, BoxError>>;
+
+    /// Sign `request` with `identity`
+    ///
+    /// Any signing parameters needed from the property bag must be read, and any results stored
+    /// into it, before returning the future.
+    fn sign(
+        &self,
+        request: http::Request<SdkBody>,
+        identity: &Identity,
+        properties: &mut PropertyBag,
+    ) -> BoxFuture<Result<http::Request<SdkBody>, BoxError>>;
+}
+
+/// The auth schemes available to sign requests, keyed by their [`AuthSchemeId`]
+///
+/// A new registry contains [`SigV4AuthScheme`], so that requests without a selected scheme can be
+/// signed.
+#[derive(Clone, Debug)]
+pub struct AuthSchemeRegistry {
+    schemes: HashMap<AuthSchemeId, Arc<dyn AuthScheme>>,
+}
+
+impl Default for AuthSchemeRegistry {
+    fn default() -> Self {
+        Self {
+            schemes: HashMap::new(),
+        }
+        .with_scheme(SigV4AuthScheme::new())
+    }
+}
+
+impl AuthSchemeRegistry {
+    /// Create a registry containing the SigV4 scheme
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `scheme`, replacing any scheme previously registered with the same ID
+    pub fn with_scheme(mut self, scheme: impl AuthScheme + 'static) -> Self {
+        self.schemes.insert(scheme.scheme_id(), Arc::new(scheme));
+        self
+    }
+
+    /// Returns the scheme registered for `scheme_id`
+    pub fn get(&self, scheme_id: AuthSchemeId) -> Option<Arc<dyn AuthScheme>> {
+        self.schemes.get(&scheme_id).cloned()
+    }
+}
+
+/// Failures that can occur in the [`AuthSchemeStage`]
+#[derive(Debug)]
+pub enum AuthSchemeStageError {
+    /// No scheme is registered with the selected ID
+    UnknownScheme(AuthSchemeId),
+    /// The selected scheme failed to resolve an identity
+    IdentityResolutionFailed(BoxError),
+    /// The selected scheme failed to sign the request
+    SigningFailed(BoxError),
+}
+
+impl fmt::Display for AuthSchemeStageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthSchemeStageError::UnknownScheme(id) => {
+                write!(f, "no auth scheme registered for `{}`", id)
+            }
+            AuthSchemeStageError::IdentityResolutionFailed(_) => {
+                write!(f, "failed to resolve an identity")
+            }
+            AuthSchemeStageError::SigningFailed(_) => write!(f, "signing failed"),
+        }
+    }
+}
+
+impl Error for AuthSchemeStageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AuthSchemeStageError::UnknownScheme(_) => None,
+            AuthSchemeStageError::IdentityResolutionFailed(err)
+            | AuthSchemeStageError::SigningFailed(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+/// Middleware stage that signs requests with the auth scheme selected for them
+///
+/// The stage:
+/// 1. Looks up the selected scheme, SigV4 by default, in the [`AuthSchemeRegistry`] from the
+///    property bag. Without a registry, only SigV4 is available.
+/// 2. Resolves an identity with the scheme and awaits it.
+/// 3. Signs the request with the scheme.
+///
+/// Failures are returned as an [`AuthSchemeStageError`], except when signing with SigV4: those
+/// are returned as is, so that they are the same
+/// [`SigningStageError`](crate::middleware::SigningStageError)s that
+/// [`SigV4SigningStage`](crate::middleware::SigV4SigningStage) returns.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct AuthSchemeStage;
+
+impl AuthSchemeStage {
+    /// Creates a new auth scheme stage
+    pub fn new() -> Self {
+        AuthSchemeStage
+    }
+
+    async fn sign(request: Request) -> Result<Request, BoxError> {
+        let scheme_id = selected_auth_scheme(&request.properties());
+        let (scheme, identity) = {
+            let properties = request.properties();
+            let scheme = match properties.get::<AuthSchemeRegistry>() {
+                Some(registry) => registry.get(scheme_id),
+                None if scheme_id == SIGV4_SCHEME_ID => {
+                    Some(Arc::new(SigV4AuthScheme::new()) as Arc<dyn AuthScheme>)
+                }
+                None => None,
+            }
+            .ok_or(AuthSchemeStageError::UnknownScheme(scheme_id))?;
+            let identity = scheme.resolve_identity(&properties);
+            (scheme, identity)
+        };
+        let identity = identity
+            .await
+            .map_err(AuthSchemeStageError::IdentityResolutionFailed)?;
+
+        let (inner, properties) = request.into_parts();
+        let signed = scheme.sign(inner, &identity, &mut properties.acquire_mut());
+        let inner = signed.await.map_err(|err| {
+            if scheme_id == SIGV4_SCHEME_ID {
+                err
+            } else {
+                AuthSchemeStageError::SigningFailed(err).into()
+            }
+        })?;
+        Ok(Request::from_parts(inner, properties))
+    }
+}
+
+impl AsyncMapRequest for AuthSchemeStage {
+    type Error = BoxError;
+    type Future = BoxFuture<Result<Request, Self::Error>>;
+
+    fn apply(&self, request: Request) -> Self::Future {
+        Box::pin(Self::sign(request))
+    }
+}
+
+/// [`AuthScheme`] that signs requests with SigV4
+///
+/// The identity is the [`Credentials`] loaded into the property bag by the credentials stage, and
+/// signing is configured by the property bag, as described for
+/// [`SigV4SigningStage`](crate::middleware::SigV4SigningStage). The resulting
+/// [`Signature`](crate::middleware::Signature) is inserted into the property bag.
+#[derive(Clone, Debug, Default)]
+pub struct SigV4AuthScheme {
+    signer: SigV4Signer,
+}
+
+impl SigV4AuthScheme {
+    /// Create a new SigV4 auth scheme
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AuthScheme for SigV4AuthScheme {
+    fn scheme_id(&self) -> AuthSchemeId {
+        SIGV4_SCHEME_ID
+    }
+
+    fn resolve_identity(&self, properties: &PropertyBag) -> BoxFuture<Result<Identity, BoxError>> {
+        // Without credentials the identity is empty: requests that don't require signing are then
+        // sent unsigned, and the others fail to sign.
+        let identity = match properties.get::<Credentials>() {
+            Some(credentials) => Identity::new(credentials.clone()),
+            None => Identity::new(()),
+        };
+        Box::pin(async move { Ok(identity) })
+    }
+
+    fn sign(
+        &self,
+        mut request: http::Request<SdkBody>,
+        identity: &Identity,
+        properties: &mut PropertyBag,
+    ) -> BoxFuture<Result<http::Request<SdkBody>, BoxError>> {
+        let signed = sign_request(
+            &self.signer,
+            &mut request,
+            properties,
+            identity.data::<Credentials>(),
+        );
+        Box::pin(async move {
+            signed?;
+            Ok(request)
+        })
+    }
+}
+
+/// An OAuth bearer token
+#[derive(Clone)]
+pub struct Token(String);
+
+impl Token {
+    /// Create a new token
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// The token itself
+    pub fn token(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(** redacted **)")
+    }
+}
+
+/// Asynchronous provider of bearer [`Token`]s
+pub trait ProvideToken: Send + Sync + fmt::Debug {
+    /// Returns a future that provides a token
+    fn provide_token(&self) -> BoxFuture<Result<Token, BoxError>>;
+}
+
+#[derive(Clone, Debug)]
+enum TokenSource {
+    Static(Token),
+    Provider(Arc<dyn ProvideToken>),
+}
+
+/// [`AuthScheme`] that signs requests with an `Authorization: Bearer <token>` header
+#[derive(Clone, Debug)]
+pub struct BearerTokenAuthScheme {
+    source: TokenSource,
+}
+
+impl BearerTokenAuthScheme {
+    /// Sign every request with the same token
+    pub fn from_static(token: impl Into<String>) -> Self {
+        Self {
+            source: TokenSource::Static(Token::new(token)),
+        }
+    }
+
+    /// Sign each request with a token from `provider`
+    pub fn from_provider(provider: impl ProvideToken + 'static) -> Self {
+        Self {
+            source: TokenSource::Provider(Arc::new(provider)),
+        }
+    }
+}
+
+impl AuthScheme for BearerTokenAuthScheme {
+    fn scheme_id(&self) -> AuthSchemeId {
+        HTTP_BEARER_AUTH_SCHEME_ID
+    }
+
+    fn resolve_identity(&self, _properties: &PropertyBag) -> BoxFuture<Result<Identity, BoxError>> {
+        match &self.source {
+            TokenSource::Static(token) => {
+                let token = token.clone();
+                Box::pin(async move { Ok(Identity::new(token)) })
+            }
+            TokenSource::Provider(provider) => {
+                let token = provider.provide_token();
+                Box::pin(async move { Ok(Identity::new(token.await?)) })
+            }
+        }
+    }
+
+    fn sign(
+        &self,
+        mut request: http::Request<SdkBody>,
+        identity: &Identity,
+        _properties: &mut PropertyBag,
+    ) -> BoxFuture<Result<http::Request<SdkBody>, BoxError>> {
+        let header = match identity.data::<Token>() {
+            Some(token) => HeaderValue::try_from(format!("Bearer {}", token.token())),
+            None => {
+                return Box::pin(async { Err("bearer auth requires a `Token` identity".into()) })
+            }
+        };
+        Box::pin(async move {
+            let mut header = header?;
+            header.set_sensitive(true);
+            request.headers_mut().insert(AUTHORIZATION, header);
+            Ok(request)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{SigV4SigningStage, Signature};
+    use crate::signer::{OperationSigningConfig, SigV4Signer};
+    use aws_smithy_http::middleware::MapRequest;
+    use aws_types::region::SigningRegion;
+    use aws_types::{Credentials, SigningService};
+    use std::convert::Infallible;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn request(scheme: Option<AuthSchemeId>) -> Request {
+        let req = http::Request::builder()
+            .uri("https://kinesis.us-east-1.amazonaws.com/")
+            .body(SdkBody::from(""))
+            .unwrap();
+        Request::new(req)
+            .augment(|req, properties| {
+                properties.insert(UNIX_EPOCH + Duration::new(1611160427, 0));
+                properties.insert(SigningService::from_static("kinesis"));
+                properties.insert(SigningRegion::from_static("us-east-1"));
+                properties.insert(OperationSigningConfig::default_config());
+                properties.insert(Credentials::new("AKIAfoo", "bar", None, None, "test"));
+                set_auth_scheme_registry(
+                    properties,
+                    AuthSchemeRegistry::new()
+                        .with_scheme(BearerTokenAuthScheme::from_static("static-token")),
+                );
+                if let Some(scheme) = scheme {
+                    set_auth_scheme(properties, scheme);
+                }
+                Result::<_, Infallible>::Ok(req)
+            })
+            .unwrap()
+    }
+
+    async fn sign(request: Request) -> Result<http::Request<SdkBody>, BoxError> {
+        let request = AuthSchemeStage::new().apply(request).await?;
+        Ok(request.into_parts().0)
+    }
+
+    #[tokio::test]
+    async fn sigv4_is_the_default() {
+        let request = sign(request(None)).await.unwrap();
+        assert!(request.headers()[AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .starts_with("AWS4-HMAC-SHA256 "));
+    }
+
+    #[tokio::test]
+    async fn sigv4_scheme_signs_like_the_signing_stage() {
+        let expected = SigV4SigningStage::new(SigV4Signer::new())
+            .apply(request(None))
+            .unwrap();
+        let mut without_registry = request(Some(SIGV4_SCHEME_ID));
+        without_registry
+            .properties_mut()
+            .remove::<AuthSchemeRegistry>();
+
+        for request in [request(None), without_registry] {
+            let signed = AuthSchemeStage::new().apply(request).await.unwrap();
+            assert_eq!(expected.http().headers(), signed.http().headers());
+            assert_eq!(
+                expected.properties().get::<Signature>().unwrap().as_ref(),
+                signed.properties().get::<Signature>().unwrap().as_ref()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bearer_token_replaces_sigv4() {
+        let request = sign(request(Some(HTTP_BEARER_AUTH_SCHEME_ID)))
+            .await
+            .unwrap();
+        assert_eq!("Bearer static-token", request.headers()[AUTHORIZATION]);
+        assert!(request.headers().get("x-amz-date").is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_scheme_is_an_error() {
+        let err = sign(request(Some(AuthSchemeId::new("example#unregistered"))))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "no auth scheme registered for `example#unregistered`",
+            err.to_string()
+        );
+    }
+
+    #[derive(Debug)]
+    struct CountingTokenProvider(std::sync::atomic::AtomicUsize);
+
+    impl ProvideToken for CountingTokenProvider {
+        fn provide_token(&self) -> BoxFuture<Result<Token, BoxError>> {
+            let n = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Token::new(format!("token-{}", n))) })
+        }
+    }
+
+    #[tokio::test]
+    async fn bearer_token_provider_is_called_per_request() {
+        let scheme =
+            BearerTokenAuthScheme::from_provider(CountingTokenProvider(Default::default()));
+        for expected in ["Bearer token-0", "Bearer token-1"] {
+            let identity = scheme.resolve_identity(&PropertyBag::new()).await.unwrap();
+            let request = scheme
+                .sign(
+                    http::Request::new(SdkBody::empty()),
+                    &identity,
+                    &mut PropertyBag::new(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(expected, request.headers()[AUTHORIZATION]);
+            assert!(request.headers()[AUTHORIZATION].is_sensitive());
+        }
+    }
+
+    #[test]
+    fn tokens_are_redacted() {
+        assert_eq!(
+            "Token(** redacted **)",
+            format!("{:?}", Token::new("secret"))
+        );
+        assert_eq!(
+            "Identity",
+            format!("{:?}", Identity::new(Token::new("secret")))
+        );
+    }
+}
@@ -8,10 +8,15 @@ use aws_sigv4::event_stream::{sign_empty_message, sign_message};
 use aws_sigv4::SigningParams;
 use aws_smithy_eventstream::frame::{Message, SignMessage, SignMessageError};
 use aws_smithy_http::property_bag::{PropertyBag, SharedPropertyBag};
+use aws_types::credentials::{self, ProvideCredentials, SharedCredentialsProvider};
 use aws_types::region::SigningRegion;
 use aws_types::Credentials;
 use aws_types::SigningService;
-use std::time::SystemTime;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 
 /// Event Stream SigV4 signing implementation.
 #[derive(Debug)]
@@ -28,16 +33,20 @@ impl SigV4Signer {
         }
     }
 
+    fn now(properties: &PropertyBag) -> SystemTime {
+        properties
+            .get::<SystemTime>()
+            .copied()
+            .unwrap_or_else(SystemTime::now)
+    }
+
     fn signing_params(properties: &PropertyBag) -> SigningParams<()> {
         // Every single one of these values would have been retrieved during the initial request,
         // so we can safely assume they all exist in the property bag at this point.
         let credentials = properties.get::<Credentials>().unwrap();
         let region = properties.get::<SigningRegion>().unwrap();
         let signing_service = properties.get::<SigningService>().unwrap();
-        let time = properties
-            .get::<SystemTime>()
-            .copied()
-            .unwrap_or_else(SystemTime::now);
+        let time = Self::now(properties);
         let mut builder = SigningParams::builder()
             .access_key(credentials.access_key_id())
             .secret_key(credentials.secret_access_key())
@@ -72,7 +81,7 @@ impl SignMessage for SigV4Signer {
         Ok(signed_message)
     }
 
-    fn sign_empty(&mut self) -> Result<Message, SignMessageError> {
+    fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
         let properties = self.properties.acquire();
         if self.last_signature.is_none() {
             // The Signature property should exist in the property bag for all Event Stream requests.
@@ -83,21 +92,114 @@ impl SignMessage for SigV4Signer {
             sign_empty_message(self.last_signature.as_ref().unwrap(), &params).into_parts()
         };
         self.last_signature = Some(signature);
-        Ok(signed_message)
+        Some(Ok(signed_message))
+    }
+}
+
+const DEFAULT_REFRESH_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+type CredentialsFuture = Pin<Box<dyn Future<Output = credentials::Result> + Send>>;
+
+/// Event Stream SigV4 signing implementation that refreshes credentials while the stream is open.
+///
+/// Messages are signed like with [`SigV4Signer`], with the credentials found in the property bag.
+/// Event streams can outlive those credentials, so before signing each message, the signer checks
+/// whether they expire within the refresh window (5 minutes by default). If they do, new
+/// credentials are loaded from the credentials provider and stored in the property bag, and the
+/// message is signed once they have been loaded. Credentials without an expiry are never
+/// refreshed.
+pub struct DeferredSigner {
+    signer: SigV4Signer,
+    provider: SharedCredentialsProvider,
+    refresh_window: Duration,
+    refresh: Option<CredentialsFuture>,
+}
+
+impl DeferredSigner {
+    pub fn new(properties: SharedPropertyBag, provider: SharedCredentialsProvider) -> Self {
+        Self {
+            signer: SigV4Signer::new(properties),
+            provider,
+            refresh_window: DEFAULT_REFRESH_WINDOW,
+            refresh: None,
+        }
+    }
+
+    /// Sets how long before their expiry credentials are refreshed.
+    pub fn with_refresh_window(mut self, refresh_window: Duration) -> Self {
+        self.refresh_window = refresh_window;
+        self
+    }
+
+    fn credentials_expire_soon(&self) -> bool {
+        let properties = self.signer.properties.acquire();
+        let expiry = properties
+            .get::<Credentials>()
+            .and_then(Credentials::expiry);
+        match expiry {
+            Some(expiry) => expiry <= SigV4Signer::now(&properties) + self.refresh_window,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for DeferredSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredSigner")
+            .field("signer", &self.signer)
+            .field("provider", &self.provider)
+            .field("refresh_window", &self.refresh_window)
+            .field("refreshing", &self.refresh.is_some())
+            .finish()
+    }
+}
+
+impl SignMessage for DeferredSigner {
+    fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+        self.signer.sign(message)
+    }
+
+    fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+        self.signer.sign_empty()
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SignMessageError>> {
+        if self.refresh.is_none() {
+            if !self.credentials_expire_soon() {
+                return Poll::Ready(Ok(()));
+            }
+            let provider = self.provider.clone();
+            self.refresh = Some(Box::pin(
+                async move { provider.provide_credentials().await },
+            ));
+        }
+        let refresh = self.refresh.as_mut().expect("set above");
+        let credentials = match refresh.as_mut().poll(cx) {
+            Poll::Ready(credentials) => credentials,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.refresh = None;
+        self.signer.properties.acquire_mut().insert(credentials?);
+        Poll::Ready(Ok(()))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::event_stream::DeferredSigner;
     use crate::event_stream::SigV4Signer;
     use crate::middleware::Signature;
     use aws_smithy_eventstream::frame::{HeaderValue, Message, SignMessage};
-    use aws_smithy_http::property_bag::PropertyBag;
+    use aws_smithy_http::property_bag::{PropertyBag, SharedPropertyBag};
+    use aws_types::credentials::{future, ProvideCredentials, SharedCredentialsProvider};
     use aws_types::region::Region;
     use aws_types::region::SigningRegion;
     use aws_types::Credentials;
     use aws_types::SigningService;
-    use std::time::{Duration, UNIX_EPOCH};
+    use futures_util::future::poll_fn;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[test]
     fn sign_message() {
@@ -132,4 +234,121 @@ mod tests {
             assert_ne!(signatures[i - 1], signatures[i]);
         }
     }
+
+    #[derive(Debug)]
+    struct RotatingProvider {
+        expiry: SystemTime,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ProvideCredentials for RotatingProvider {
+        fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+        where
+            Self: 'a,
+        {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ProvideCredentials::new(async move {
+                Ok(Credentials::new(
+                    format!("AKIDrotated{}", call),
+                    format!("secret{}", call),
+                    None,
+                    Some(self.expiry),
+                    "test",
+                ))
+            })
+        }
+    }
+
+    fn chunk_signature(message: &Message) -> String {
+        match message
+            .headers()
+            .iter()
+            .find(|h| h.name().as_str() == ":chunk-signature")
+            .unwrap()
+            .value()
+        {
+            HeaderValue::ByteArray(signature) => signature
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            _ => panic!("failed to get the :chunk-signature"),
+        }
+    }
+
+    /// Returns whether `signed` is `message` signed with the given keys
+    fn is_signed_with(
+        signed: &Message,
+        message: &Message,
+        last_signature: &str,
+        time: SystemTime,
+        (access_key, secret_key): (&str, &str),
+    ) -> bool {
+        let params = aws_sigv4::SigningParams::builder()
+            .access_key(access_key)
+            .secret_key(secret_key)
+            .region("us-east-1")
+            .service_name("transcribe")
+            .time(time)
+            .settings(())
+            .build()
+            .unwrap();
+        let (expected, _) =
+            aws_sigv4::event_stream::sign_message(message, last_signature, &params).into_parts();
+        chunk_signature(&expected) == chunk_signature(signed)
+    }
+
+    #[tokio::test]
+    async fn deferred_signer_refreshes_expiring_credentials() {
+        let start = UNIX_EPOCH + Duration::new(1611160427, 0);
+        let region = Region::new("us-east-1");
+        let mut properties = PropertyBag::new();
+        properties.insert(region.clone());
+        properties.insert(start);
+        properties.insert(SigningService::from_static("transcribe"));
+        properties.insert(Credentials::new(
+            "AKIDinitial",
+            "secret0",
+            None,
+            Some(start + Duration::from_secs(10 * 60)),
+            "test",
+        ));
+        properties.insert(SigningRegion::from(region));
+        properties.insert(Signature::new("initial-signature".into()));
+        let properties = SharedPropertyBag::from(properties);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = RotatingProvider {
+            expiry: start + Duration::from_secs(60 * 60),
+            calls: calls.clone(),
+        };
+        let mut signer =
+            DeferredSigner::new(properties.clone(), SharedCredentialsProvider::new(provider));
+
+        let message = Message::new(&b"identical message"[..]);
+        let mut last_signature = "initial-signature".to_string();
+        for frame in 0..5 {
+            if frame == 3 {
+                // The initial credentials are now within the refresh window
+                properties
+                    .acquire_mut()
+                    .insert(start + Duration::from_secs(6 * 60));
+            }
+            poll_fn(|cx| signer.poll_ready(cx)).await.unwrap();
+            let signed = signer.sign(message.clone()).unwrap();
+            let time = *properties.acquire().get::<SystemTime>().unwrap();
+            let keys = if frame < 3 {
+                ("AKIDinitial", "secret0")
+            } else {
+                ("AKIDrotated1", "secret1")
+            };
+            assert!(
+                is_signed_with(&signed, &message, &last_signature, time, keys),
+                "frame {} wasn't signed with {:?}",
+                frame,
+                keys
+            );
+            last_signature = chunk_signature(&signed);
+        }
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
 }
@@ -106,6 +106,8 @@
 //! }
 //! ```
 
+pub mod auth_scheme;
+
 #[cfg(feature = "sign-eventstream")]
 pub mod event_stream;
 
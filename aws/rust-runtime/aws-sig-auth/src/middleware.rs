@@ -7,6 +7,7 @@ use crate::signer::{
     OperationSigningConfig, RequestConfig, SigV4Signer, SigningError, SigningRequirements,
 };
 use aws_sigv4::http_request::SignableBody;
+use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::middleware::MapRequest;
 use aws_smithy_http::operation::Request;
 use aws_smithy_http::property_bag::PropertyBag;
@@ -49,6 +50,9 @@ impl AsRef<str> for Signature {
 /// The following fields MAY be present in the property bag:
 /// - [`SystemTime`](SystemTime): The timestamp to use when signing the request. If this field is not present
 ///   [`SystemTime::now`](SystemTime::now) will be used.
+///
+/// The same signing is available as an [auth scheme](crate::auth_scheme), see
+/// [`SigV4AuthScheme`](crate::auth_scheme::SigV4AuthScheme).
 #[derive(Clone, Debug)]
 pub struct SigV4SigningStage {
     signer: SigV4Signer,
@@ -110,14 +114,14 @@ impl Error for SigningStageError {
 }
 
 /// Extract a signing config from a [`PropertyBag`](aws_smithy_http::property_bag::PropertyBag)
-fn signing_config(
-    config: &PropertyBag,
-) -> Result<(&OperationSigningConfig, RequestConfig, Credentials), SigningStageError> {
+fn signing_config<'a>(
+    config: &'a PropertyBag,
+    credentials: Option<&Credentials>,
+) -> Result<(&'a OperationSigningConfig, RequestConfig<'a>, Credentials), SigningStageError> {
     let operation_config = config
         .get::<OperationSigningConfig>()
         .ok_or(SigningStageError::MissingSigningConfig)?;
-    let credentials = config
-        .get::<Credentials>()
+    let credentials = credentials
         .ok_or(SigningStageError::MissingCredentials)?
         .clone();
     let region = config
@@ -139,29 +143,40 @@ fn signing_config(
     Ok((operation_config, request_config, credentials))
 }
 
+/// Sign `req` with `credentials`, as configured by the property bag, and insert the resulting
+/// [`Signature`] into it
+pub(crate) fn sign_request(
+    signer: &SigV4Signer,
+    req: &mut http::Request<SdkBody>,
+    config: &mut PropertyBag,
+    credentials: Option<&Credentials>,
+) -> Result<(), SigningStageError> {
+    let operation_config = config
+        .get::<OperationSigningConfig>()
+        .ok_or(SigningStageError::MissingSigningConfig)?;
+    let (operation_config, request_config, creds) = match &operation_config.signing_requirements {
+        SigningRequirements::Disabled => return Ok(()),
+        SigningRequirements::Optional => match signing_config(config, credentials) {
+            Ok(parts) => parts,
+            Err(_) => return Ok(()),
+        },
+        SigningRequirements::Required => signing_config(config, credentials)?,
+    };
+
+    let signature = signer
+        .sign(operation_config, &request_config, &creds, req)
+        .map_err(SigningStageError::SigningFailure)?;
+    config.insert(signature);
+    Ok(())
+}
+
 impl MapRequest for SigV4SigningStage {
     type Error = SigningStageError;
 
     fn apply(&self, req: Request) -> Result<Request, Self::Error> {
         req.augment(|mut req, config| {
-            let operation_config = config
-                .get::<OperationSigningConfig>()
-                .ok_or(SigningStageError::MissingSigningConfig)?;
-            let (operation_config, request_config, creds) =
-                match &operation_config.signing_requirements {
-                    SigningRequirements::Disabled => return Ok(req),
-                    SigningRequirements::Optional => match signing_config(config) {
-                        Ok(parts) => parts,
-                        Err(_) => return Ok(req),
-                    },
-                    SigningRequirements::Required => signing_config(config)?,
-                };
-
-            let signature = self
-                .signer
-                .sign(operation_config, &request_config, &creds, &mut req)
-                .map_err(|err| SigningStageError::SigningFailure(err))?;
-            config.insert(signature);
+            let credentials = config.get::<Credentials>().cloned();
+            sign_request(&self.signer, &mut req, config, credentials.as_ref())?;
             Ok(req)
         })
     }
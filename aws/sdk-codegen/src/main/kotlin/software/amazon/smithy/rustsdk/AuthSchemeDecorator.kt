@@ -0,0 +1,115 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+package software.amazon.smithy.rustsdk
+
+import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.rust.codegen.rustlang.Writable
+import software.amazon.smithy.rust.codegen.rustlang.rustTemplate
+import software.amazon.smithy.rust.codegen.rustlang.writable
+import software.amazon.smithy.rust.codegen.smithy.ClientCodegenContext
+import software.amazon.smithy.rust.codegen.smithy.RuntimeConfig
+import software.amazon.smithy.rust.codegen.smithy.RuntimeType
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationCustomization
+import software.amazon.smithy.rust.codegen.smithy.customize.OperationSection
+import software.amazon.smithy.rust.codegen.smithy.customize.RustCodegenDecorator
+import software.amazon.smithy.rust.codegen.smithy.generators.config.ConfigCustomization
+import software.amazon.smithy.rust.codegen.smithy.generators.config.ServiceConfig
+
+/**
+ * The AuthSchemeDecorator:
+ * - adds an `auth_scheme_registry` to `config`, holding the auth schemes requests can be signed with
+ * - places the registry into the property bag of every operation, for the auth scheme stage to sign with
+ */
+class AuthSchemeDecorator : RustCodegenDecorator<ClientCodegenContext> {
+    override val name: String = "AuthScheme"
+    override val order: Byte = 0
+
+    override fun configCustomizations(
+        codegenContext: ClientCodegenContext,
+        baseCustomizations: List<ConfigCustomization>
+    ): List<ConfigCustomization> {
+        return baseCustomizations + AuthSchemeConfig(codegenContext.runtimeConfig)
+    }
+
+    override fun operationCustomizations(
+        codegenContext: ClientCodegenContext,
+        operation: OperationShape,
+        baseCustomizations: List<OperationCustomization>
+    ): List<OperationCustomization> {
+        return baseCustomizations + AuthSchemeFeature(codegenContext.runtimeConfig)
+    }
+}
+
+/**
+ * Add a `.auth_scheme_registry` field and builder to the `Config` for a given service
+ */
+class AuthSchemeConfig(runtimeConfig: RuntimeConfig) : ConfigCustomization() {
+    private val codegenScope = arrayOf(
+        "AuthSchemeRegistry" to authSchemeRegistry(runtimeConfig)
+    )
+
+    override fun section(section: ServiceConfig) = writable {
+        when (section) {
+            is ServiceConfig.ConfigStruct -> rustTemplate(
+                "pub(crate) auth_scheme_registry: #{AuthSchemeRegistry},",
+                *codegenScope
+            )
+            is ServiceConfig.ConfigImpl -> emptySection
+            is ServiceConfig.BuilderStruct ->
+                rustTemplate("auth_scheme_registry: Option<#{AuthSchemeRegistry}>,", *codegenScope)
+            ServiceConfig.BuilderImpl -> {
+                rustTemplate(
+                    """
+                    /// Sets the auth schemes that requests can be signed with
+                    ///
+                    /// The registry must contain the auth scheme selected for each operation. By default, it only
+                    /// contains SigV4, which is used when no other scheme is selected.
+                    pub fn auth_scheme_registry(mut self, auth_scheme_registry: #{AuthSchemeRegistry}) -> Self {
+                        self.auth_scheme_registry = Some(auth_scheme_registry);
+                        self
+                    }
+
+                    /// Sets the auth schemes that requests can be signed with
+                    ///
+                    /// The registry must contain the auth scheme selected for each operation. By default, it only
+                    /// contains SigV4, which is used when no other scheme is selected.
+                    pub fn set_auth_scheme_registry(&mut self, auth_scheme_registry: Option<#{AuthSchemeRegistry}>) -> &mut Self {
+                        self.auth_scheme_registry = auth_scheme_registry;
+                        self
+                    }
+                    """,
+                    *codegenScope,
+                )
+            }
+            ServiceConfig.BuilderBuild -> rustTemplate(
+                "auth_scheme_registry: self.auth_scheme_registry.unwrap_or_default(),",
+                *codegenScope
+            )
+        }
+    }
+}
+
+class AuthSchemeFeature(private val runtimeConfig: RuntimeConfig) : OperationCustomization() {
+    override fun section(section: OperationSection): Writable {
+        return when (section) {
+            is OperationSection.MutateRequest -> writable {
+                rustTemplate(
+                    """
+                    #{set_auth_scheme_registry}(&mut ${section.request}.properties_mut(), ${section.config}.auth_scheme_registry.clone());
+                    """,
+                    "set_auth_scheme_registry" to setAuthSchemeRegistry(runtimeConfig)
+                )
+            }
+            else -> emptySection
+        }
+    }
+}
+
+fun authSchemeRegistry(runtimeConfig: RuntimeConfig) =
+    RuntimeType("AuthSchemeRegistry", runtimeConfig.sigAuth(), "aws_sig_auth::auth_scheme")
+
+fun setAuthSchemeRegistry(runtimeConfig: RuntimeConfig) =
+    RuntimeType("set_auth_scheme_registry", runtimeConfig.sigAuth(), "aws_sig_auth::auth_scheme")
@@ -509,9 +509,11 @@ private class ServerHttpBoundProtocolTraitImplGenerator(
 
         operationShape.outputShape(model).findStreamingMember(model)?.let {
             val memberName = symbolProvider.toMemberName(it)
+            // Box the `ByteStream`'s `SdkBody` directly so that trailers (e.g. checksums) produced by its
+            // callbacks are sent.
             rustTemplate(
                 """
-                let payload = #{SmithyHttpServer}::body::Body::wrap_stream(output.$memberName);
+                let body = #{SmithyHttpServer}::body::from_byte_stream(output.$memberName);
                 """,
                 *codegenScope,
             )
@@ -522,15 +524,10 @@ private class ServerHttpBoundProtocolTraitImplGenerator(
             }
 
             serverRenderContentLengthHeader()
+            rustTemplate("let body = #{SmithyHttpServer}::body::to_boxed(payload);", *codegenScope)
         }
 
-        rustTemplate(
-            """
-            let body = #{SmithyHttpServer}::body::to_boxed(payload);
-            builder.body(body)?
-            """,
-            *codegenScope,
-        )
+        rust("builder.body(body)?")
     }
 
     /**
@@ -151,7 +151,7 @@ class EventStreamUnmarshallerGenerator(
                         *codegenScope
                     )
                     false -> rustTemplate(
-                        "return Err(#{Error}::Unmarshalling(format!(\"unrecognized :event-type: {}\", _unknown_variant)));",
+                        "return Err(#{Error}::UnknownEventType(_unknown_variant.into()));",
                         *codegenScope
                     )
                 }
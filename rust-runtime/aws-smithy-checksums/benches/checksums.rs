@@ -0,0 +1,63 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_smithy_checksums::try_new_checksum;
+use aws_smithy_http::body::SdkBody;
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use http_body::Body;
+
+const BODY_SIZE: usize = 16 * 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Checksums a body made of `CHUNK_SIZE` chunks through `SdkBody::with_callback`, as done when
+/// uploading it.
+async fn checksum_body(algorithm: &str, data: &Bytes) {
+    let chunks: Vec<Result<Bytes, std::io::Error>> = (0..data.len())
+        .step_by(CHUNK_SIZE)
+        .map(|start| Ok(data.slice(start..(start + CHUNK_SIZE).min(data.len()))))
+        .collect();
+    let mut body = SdkBody::from(hyper::Body::wrap_stream(futures_util::stream::iter(chunks)));
+    body.with_callback(Box::new(try_new_checksum(algorithm).unwrap()));
+    while let Some(chunk) = body.data().await {
+        chunk.unwrap();
+    }
+    body.trailers().await.unwrap().unwrap();
+}
+
+fn checksums(c: &mut Criterion) {
+    let data = Bytes::from(vec![0xa5; BODY_SIZE]);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("checksum_16MiB");
+    group.throughput(Throughput::Bytes(BODY_SIZE as u64));
+    group.sample_size(10);
+    for algorithm in ["crc32", "crc32c", "sha1", "sha256"] {
+        group.bench_with_input(
+            BenchmarkId::new("update", algorithm),
+            algorithm,
+            |b, algorithm| {
+                b.iter(|| {
+                    let mut checksum = try_new_checksum(algorithm).unwrap();
+                    for chunk in data.chunks(CHUNK_SIZE) {
+                        checksum.update(chunk).unwrap();
+                    }
+                    checksum.trailers().unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("body", algorithm),
+            algorithm,
+            |b, algorithm| b.iter(|| runtime.block_on(checksum_body(algorithm, &data))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, checksums);
+criterion_main!(benches);
@@ -0,0 +1,627 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Coalescing of identical concurrent requests
+//!
+//! When many tasks request the same object at the same moment, a [`CoalescingLayer`] sends only
+//! one of those requests over the wire (the _leader_) and makes the others (the _followers_) wait
+//! for its response. The leader's response body is buffered and a copy of it is handed to every
+//! follower.
+//!
+//! Only `GET` and `HEAD` requests without a body are coalesced. Requests are identical when their
+//! method, URI, and [key headers](CoalescingLayer::with_key_header) match.
+//!
+//! - If the leader's response body is larger than the
+//!   [maximum buffered size](CoalescingLayer::with_max_buffered_bytes), the leader's response is
+//!   streamed to the leader only, and each follower sends its own request.
+//! - If the leader's request fails, every follower fails with a [`ConnectorError`] of the same
+//!   kind whose source is a [`CoalescedRequestFailed`].
+//! - If the leader's request is cancelled, its followers send their own requests.
+//!
+//! Since credentials are not part of the key, a coalescing connector should only be shared by
+//! clients that sign with the same identity.
+//!
+//! ```no_run
+//! use aws_smithy_client::coalesce::CoalescingLayer;
+//! use tower::Layer;
+//!
+//! # #[cfg(feature = "rustls")]
+//! # fn example() {
+//! let conn = CoalescingLayer::new().layer(aws_smithy_client::conns::https());
+//! # }
+//! ```
+
+use aws_smithy_http::body::{BoxBody, Error as BodyError, SdkBody};
+use aws_smithy_http::result::ConnectorError;
+use bytes::{Bytes, BytesMut};
+use http::header::{
+    HeaderName, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, RANGE,
+};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, Version};
+use http_body::Body;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+use tower::{Layer, Service, ServiceExt};
+
+const DEFAULT_MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+/// Identifies requests that can share a response
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct Key {
+    method: Method,
+    uri: Uri,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// What the leader tells its followers once its request completes
+#[derive(Debug)]
+enum Outcome {
+    Buffered {
+        status: StatusCode,
+        version: Version,
+        headers: HeaderMap,
+        body: Bytes,
+    },
+    TooLarge,
+    Failed {
+        kind: FailureKind,
+        message: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum FailureKind {
+    Timeout,
+    Io,
+    User,
+    Other(Option<aws_smithy_types::retry::ErrorKind>),
+}
+
+type Waiter = watch::Receiver<Option<Arc<Outcome>>>;
+
+#[derive(Debug, Default)]
+struct Stats {
+    leaders: AtomicU64,
+    followers: AtomicU64,
+    oversized: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Shared {
+    in_flight: Mutex<HashMap<Key, Waiter>>,
+    key_headers: Vec<HeaderName>,
+    max_buffered_bytes: usize,
+    stats: Stats,
+}
+
+/// Counts of coalesced requests, as reported by [`CoalescingLayer::stats`]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CoalescingStats {
+    /// Requests that were sent over the wire on behalf of themselves and any followers
+    pub leaders: u64,
+    /// Requests that waited for a leader instead of being sent
+    pub followers: u64,
+    /// Leader responses that were too large to be shared with followers
+    pub oversized: u64,
+}
+
+/// A [`tower::Layer`] for connectors that coalesces identical concurrent `GET` requests
+///
+/// See the [module documentation](crate::coalesce) for details.
+#[derive(Clone, Debug)]
+pub struct CoalescingLayer {
+    shared: Arc<Shared>,
+}
+
+impl Default for CoalescingLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoalescingLayer {
+    /// Create a new coalescing layer
+    ///
+    /// By default, responses of up to 1 MiB are shared, and the `Range`, `If-Match`,
+    /// `If-None-Match`, `If-Modified-Since`, and `If-Unmodified-Since` headers are part of the key.
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                in_flight: Default::default(),
+                key_headers: vec![
+                    RANGE,
+                    IF_MATCH,
+                    IF_NONE_MATCH,
+                    IF_MODIFIED_SINCE,
+                    IF_UNMODIFIED_SINCE,
+                ],
+                max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+                stats: Default::default(),
+            }),
+        }
+    }
+
+    fn shared_mut(&mut self) -> &mut Shared {
+        Arc::get_mut(&mut self.shared).expect("layer is only configured before it is cloned")
+    }
+
+    /// Only share responses with bodies of at most `max_buffered_bytes`
+    pub fn with_max_buffered_bytes(mut self, max_buffered_bytes: usize) -> Self {
+        self.shared_mut().max_buffered_bytes = max_buffered_bytes;
+        self
+    }
+
+    /// Only coalesce requests whose values for `header` match
+    pub fn with_key_header(mut self, header: HeaderName) -> Self {
+        self.shared_mut().key_headers.push(header);
+        self
+    }
+
+    /// Counts of the requests coalesced so far
+    pub fn stats(&self) -> CoalescingStats {
+        let stats = &self.shared.stats;
+        CoalescingStats {
+            leaders: stats.leaders.load(Ordering::Relaxed),
+            followers: stats.followers.load(Ordering::Relaxed),
+            oversized: stats.oversized.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<S> Layer<S> for CoalescingLayer {
+    type Service = Coalescing<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Coalescing {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Connector created by [`CoalescingLayer`]
+#[derive(Clone, Debug)]
+pub struct Coalescing<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl Shared {
+    fn key(&self, request: &http::Request<SdkBody>) -> Option<Key> {
+        let coalescible = matches!(*request.method(), Method::GET | Method::HEAD)
+            && request.body().content_length() == Some(0);
+        if !coalescible {
+            return None;
+        }
+        let headers = self
+            .key_headers
+            .iter()
+            .flat_map(|name| {
+                request
+                    .headers()
+                    .get_all(name)
+                    .iter()
+                    .map(move |value| (name.clone(), value.clone()))
+            })
+            .collect();
+        Some(Key {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            headers,
+        })
+    }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+impl<S> Service<http::Request<SdkBody>> for Coalescing<S>
+where
+    S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>> + Clone + Send + 'static,
+    S::Error: Into<ConnectorError>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let key = match self.shared.key(&req) {
+            Some(key) => key,
+            None => {
+                let fut = self.inner.call(req);
+                return Box::pin(async move { fut.await.map_err(Into::into) });
+            }
+        };
+
+        let mut in_flight = self.shared.in_flight.lock().unwrap();
+        if let Some(waiter) = in_flight.get(&key) {
+            self.shared.stats.followers.fetch_add(1, Ordering::Relaxed);
+            return Box::pin(follow(waiter.clone(), self.inner.clone(), req));
+        }
+        let (sender, waiter) = watch::channel(None);
+        in_flight.insert(key.clone(), waiter);
+        drop(in_flight);
+        self.shared.stats.leaders.fetch_add(1, Ordering::Relaxed);
+
+        let guard = InFlightGuard {
+            shared: self.shared.clone(),
+            key,
+        };
+        // `call` must be used here since `poll_ready` was called on `self.inner`
+        let fut = self.inner.call(req);
+        Box::pin(lead(fut, sender, guard))
+    }
+}
+
+/// Removes the leader's entry from the in-flight requests when it completes or is cancelled
+struct InFlightGuard {
+    shared: Arc<Shared>,
+    key: Key,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.shared.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+async fn lead<F, E>(
+    fut: F,
+    sender: watch::Sender<Option<Arc<Outcome>>>,
+    guard: InFlightGuard,
+) -> Result<http::Response<SdkBody>, ConnectorError>
+where
+    F: Future<Output = Result<http::Response<SdkBody>, E>>,
+    E: Into<ConnectorError>,
+{
+    let response = match fut.await {
+        Ok(response) => response,
+        Err(err) => {
+            let err = err.into();
+            let _ = sender.send(Some(Arc::new(Outcome::Failed {
+                kind: FailureKind::of(&err),
+                message: error_chain(&err),
+            })));
+            return Err(err);
+        }
+    };
+
+    let (parts, mut body) = response.into_parts();
+    let mut buffered = BytesMut::new();
+    while let Some(data) = body.data().await {
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                let err = ConnectorError::io(err);
+                let _ = sender.send(Some(Arc::new(Outcome::Failed {
+                    kind: FailureKind::Io,
+                    message: error_chain(&err),
+                })));
+                return Err(err);
+            }
+        };
+        buffered.extend_from_slice(&data);
+        if buffered.len() > guard.shared.max_buffered_bytes {
+            guard.shared.stats.oversized.fetch_add(1, Ordering::Relaxed);
+            let _ = sender.send(Some(Arc::new(Outcome::TooLarge)));
+            let body = Prefixed {
+                prefix: Some(buffered.freeze()),
+                rest: body,
+            };
+            return Ok(http::Response::from_parts(
+                parts,
+                SdkBody::from_dyn(BoxBody::new(body)),
+            ));
+        }
+    }
+
+    let body = buffered.freeze();
+    let _ = sender.send(Some(Arc::new(Outcome::Buffered {
+        status: parts.status,
+        version: parts.version,
+        headers: parts.headers.clone(),
+        body: body.clone(),
+    })));
+    Ok(http::Response::from_parts(parts, SdkBody::from(body)))
+}
+
+async fn follow<S>(
+    mut waiter: Waiter,
+    inner: S,
+    req: http::Request<SdkBody>,
+) -> Result<http::Response<SdkBody>, ConnectorError>
+where
+    S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    S::Error: Into<ConnectorError>,
+{
+    let outcome = loop {
+        if let Some(outcome) = waiter.borrow().clone() {
+            break Some(outcome);
+        }
+        if waiter.changed().await.is_err() {
+            // The leader went away; it may still have sent an outcome before doing so
+            break waiter.borrow().clone();
+        }
+    };
+    match outcome.as_deref() {
+        Some(Outcome::Buffered {
+            status,
+            version,
+            headers,
+            body,
+        }) => {
+            let mut response = http::Response::new(SdkBody::from(body.clone()));
+            *response.status_mut() = *status;
+            *response.version_mut() = *version;
+            *response.headers_mut() = headers.clone();
+            Ok(response)
+        }
+        Some(Outcome::Failed { kind, message }) => Err(kind.to_error(CoalescedRequestFailed {
+            message: message.clone(),
+        })),
+        // Either the response can't be shared, or the leader was cancelled
+        Some(Outcome::TooLarge) | None => inner.oneshot(req).await.map_err(Into::into),
+    }
+}
+
+impl FailureKind {
+    fn of(err: &ConnectorError) -> Self {
+        if err.is_timeout() {
+            FailureKind::Timeout
+        } else if err.is_io() {
+            FailureKind::Io
+        } else if err.is_user() {
+            FailureKind::User
+        } else {
+            FailureKind::Other(err.is_other())
+        }
+    }
+
+    fn to_error(self, err: CoalescedRequestFailed) -> ConnectorError {
+        let err = Box::new(err);
+        match self {
+            FailureKind::Timeout => ConnectorError::timeout(err),
+            FailureKind::Io => ConnectorError::io(err),
+            FailureKind::User => ConnectorError::user(err),
+            FailureKind::Other(kind) => ConnectorError::other(err, kind),
+        }
+    }
+}
+
+fn error_chain(err: &(dyn Error + 'static)) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+/// The source of errors returned to requests that were coalesced with a request that failed
+#[derive(Debug)]
+pub struct CoalescedRequestFailed {
+    message: String,
+}
+
+impl fmt::Display for CoalescedRequestFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "this request was coalesced with a request that failed: {}",
+            self.message
+        )
+    }
+}
+
+impl Error for CoalescedRequestFailed {}
+
+pin_project_lite::pin_project! {
+    /// Replays the part of a body that has already been read before the rest of it
+    struct Prefixed {
+        prefix: Option<Bytes>,
+        #[pin]
+        rest: SdkBody,
+    }
+}
+
+impl Body for Prefixed {
+    type Data = Bytes;
+    type Error = BodyError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        match this.prefix.take() {
+            Some(prefix) => Poll::Ready(Some(Ok(prefix))),
+            None => this.rest.poll_data(cx),
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
+        self.project().rest.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.prefix.is_none() && self.rest.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::sync::Semaphore;
+
+    /// Counts requests, and only responds to each of them once `release` has a permit for it
+    #[derive(Clone)]
+    struct GatedConnection {
+        requests: Arc<AtomicUsize>,
+        release: Arc<Semaphore>,
+        response: Result<&'static str, &'static str>,
+    }
+
+    impl GatedConnection {
+        fn new(response: Result<&'static str, &'static str>) -> Self {
+            Self {
+                requests: Default::default(),
+                release: Arc::new(Semaphore::new(0)),
+                response,
+            }
+        }
+    }
+
+    impl Service<http::Request<SdkBody>> for GatedConnection {
+        type Response = http::Response<SdkBody>;
+        type Error = ConnectorError;
+        type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<SdkBody>) -> Self::Future {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let release = self.release.clone();
+            let response = self.response;
+            Box::pin(async move {
+                release.acquire().await.unwrap().forget();
+                match response {
+                    Ok(body) => Ok(http::Response::builder()
+                        .status(200)
+                        .header("etag", "abc")
+                        .body(SdkBody::from(body))
+                        .unwrap()),
+                    Err(err) => Err(ConnectorError::timeout(err.into())),
+                }
+            })
+        }
+    }
+
+    fn get(uri: &str) -> http::Request<SdkBody> {
+        http::Request::get(uri).body(SdkBody::empty()).unwrap()
+    }
+
+    /// Sends `n` identical requests and waits until they have all been dispatched to the
+    /// connection, or are waiting on a leader, before releasing the connection
+    async fn send_concurrently(
+        layer: &CoalescingLayer,
+        conn: &GatedConnection,
+        n: usize,
+    ) -> Vec<Result<http::Response<SdkBody>, ConnectorError>> {
+        let tasks: Vec<_> = (0..n)
+            .map(|_| {
+                tokio::spawn(
+                    layer
+                        .layer(conn.clone())
+                        .oneshot(get("https://example.com/key")),
+                )
+            })
+            .collect();
+        while (layer.stats().leaders + layer.stats().followers) < n as u64 {
+            tokio::task::yield_now().await;
+        }
+        // Followers that fall back to sending their own request need to be released too
+        conn.release.add_permits(n);
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn identical_concurrent_gets_are_sent_once() {
+        let layer = CoalescingLayer::new();
+        let conn = GatedConnection::new(Ok("shared body"));
+
+        for response in send_concurrently(&layer, &conn, 5).await {
+            let response = response.unwrap();
+            assert_eq!(200, response.status());
+            assert_eq!("abc", response.headers()["etag"]);
+            assert_eq!(b"shared body", response.body().bytes().unwrap());
+        }
+        assert_eq!(1, conn.requests.load(Ordering::SeqCst));
+        assert_eq!(
+            CoalescingStats {
+                leaders: 1,
+                followers: 4,
+                oversized: 0
+            },
+            layer.stats()
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_responses_are_fetched_independently() {
+        let layer = CoalescingLayer::new().with_max_buffered_bytes(4);
+        let conn = GatedConnection::new(Ok("too large to share"));
+
+        for response in send_concurrently(&layer, &conn, 3).await {
+            let body = response.unwrap().into_body();
+            let body = collect_body(body).await;
+            assert_eq!(&b"too large to share"[..], &body[..]);
+        }
+        assert_eq!(3, conn.requests.load(Ordering::SeqCst));
+        assert_eq!(1, layer.stats().oversized);
+    }
+
+    #[tokio::test]
+    async fn errors_propagate_to_all_waiters() {
+        let layer = CoalescingLayer::new();
+        let conn = GatedConnection::new(Err("slow"));
+
+        let errors: Vec<_> = send_concurrently(&layer, &conn, 3)
+            .await
+            .into_iter()
+            .map(|response| response.unwrap_err())
+            .collect();
+        assert_eq!(1, conn.requests.load(Ordering::SeqCst));
+        assert!(errors.iter().all(|err| err.is_timeout()));
+        let coalesced = errors
+            .iter()
+            .filter_map(|err| err.source()?.downcast_ref::<CoalescedRequestFailed>())
+            .count();
+        assert_eq!(2, coalesced);
+    }
+
+    #[tokio::test]
+    async fn requests_with_bodies_are_not_coalesced() {
+        let layer = CoalescingLayer::new();
+        let conn = GatedConnection::new(Ok(""));
+        conn.release.add_permits(1);
+        let request = http::Request::put("https://example.com/key")
+            .body(SdkBody::from("data"))
+            .unwrap();
+        layer.layer(conn.clone()).oneshot(request).await.unwrap();
+        assert_eq!(CoalescingStats::default(), layer.stats());
+    }
+
+    async fn collect_body(mut body: SdkBody) -> Bytes {
+        let mut output = BytesMut::new();
+        while let Some(data) = body.data().await {
+            output.extend_from_slice(&data.unwrap());
+        }
+        output.freeze()
+    }
+}
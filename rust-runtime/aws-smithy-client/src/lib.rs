@@ -22,6 +22,7 @@
 )]
 
 pub mod bounds;
+pub mod coalesce;
 pub mod erase;
 pub mod retry;
 
@@ -14,6 +14,8 @@ use aws_smithy_protocol_test::{assert_ok, validate_body, MediaType};
 
 use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::result::ConnectorError;
+use std::error::Error;
+use std::fmt;
 use std::future::Ready;
 
 use std::ops::Deref;
@@ -105,12 +107,184 @@ pub fn capture_request(
     )
 }
 
-type ConnectVec<B> = Vec<(http::Request<SdkBody>, http::Response<B>)>;
+type ConnectVec<B, ReqBody> = Vec<(http::Request<ReqBody>, http::Response<B>)>;
+type ExpectationVec<B, ReqBody> = Vec<(Expectation<ReqBody>, http::Response<B>)>;
+
+/// What a [`TestConnection`] expects of the next request
+enum Expectation<ReqBody> {
+    /// The request is recorded, to be compared in full with
+    /// [`assert_requests_match`](TestConnection::assert_requests_match)
+    Request(http::Request<ReqBody>),
+    /// The request is checked against the matcher when it is made
+    Matcher(RequestMatcher, fn(&ReqBody) -> Option<&[u8]>),
+}
+
+impl<ReqBody: fmt::Debug> fmt::Debug for Expectation<ReqBody> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Request(request) => f.debug_tuple("Request").field(request).finish(),
+            Expectation::Matcher(matcher, _) => f.debug_tuple("Matcher").field(matcher).finish(),
+        }
+    }
+}
+
+/// Checks a subset of a request, for use with [`TestConnection::new_with_matchers`]
+///
+/// ```
+/// use aws_smithy_client::test_connection::RequestMatcher;
+///
+/// let matcher = RequestMatcher::new()
+///     .method(http::Method::PUT)
+///     .path("/bucket/key")
+///     .header("content-type", "application/json")
+///     .body_contains(b"\"key\":");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RequestMatcher {
+    method: Option<http::Method>,
+    path: Option<String>,
+    headers: Vec<(HeaderName, String)>,
+    body_contains: Vec<Vec<u8>>,
+}
+
+impl RequestMatcher {
+    /// Create a matcher that matches any request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require the request to use `method`
+    pub fn method(mut self, method: http::Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Require the path of the request URI to equal `path`
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Require the request to have a header `name` equal to `value`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a valid header name.
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("valid header name");
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Require the request body to contain `bytes`
+    pub fn body_contains(mut self, bytes: &[u8]) -> Self {
+        self.body_contains.push(bytes.to_vec());
+        self
+    }
+
+    /// Check `request` against this matcher, describing everything that didn't match
+    pub fn check(&self, request: &http::Request<SdkBody>) -> Result<(), RequestMismatch> {
+        self.check_with_body(request, request.body().bytes())
+    }
+
+    fn check_with_body<ReqBody>(
+        &self,
+        request: &http::Request<ReqBody>,
+        body: Option<&[u8]>,
+    ) -> Result<(), RequestMismatch> {
+        let mut mismatches = Vec::new();
+        if let Some(method) = &self.method {
+            if method != request.method() {
+                mismatches.push(format!(
+                    "expected method {}, got {}",
+                    method,
+                    request.method()
+                ));
+            }
+        }
+        if let Some(path) = &self.path {
+            if path != request.uri().path() {
+                mismatches.push(format!(
+                    "expected path `{}`, got `{}`",
+                    path,
+                    request.uri().path()
+                ));
+            }
+        }
+        for (name, expected) in &self.headers {
+            match request.headers().get(name) {
+                None => mismatches.push(format!(
+                    "expected header `{}` to be `{}`, but it was missing",
+                    name, expected
+                )),
+                Some(actual) if actual.as_bytes() != expected.as_bytes() => {
+                    mismatches.push(format!(
+                        "expected header `{}` to be `{}`, got `{}`",
+                        name,
+                        expected,
+                        String::from_utf8_lossy(actual.as_bytes())
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        for needle in &self.body_contains {
+            let found = body.map(|body| {
+                needle.is_empty() || body.windows(needle.len()).any(|window| window == needle)
+            });
+            match found {
+                Some(true) => {}
+                Some(false) => mismatches.push(format!(
+                    "expected the body to contain `{}`",
+                    String::from_utf8_lossy(needle)
+                )),
+                None => mismatches.push(format!(
+                    "expected the body to contain `{}`, but it is streaming",
+                    String::from_utf8_lossy(needle)
+                )),
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(RequestMismatch { mismatches })
+        }
+    }
+}
+
+/// Error returned when a request doesn't match a [`RequestMatcher`]
+#[derive(Debug)]
+pub struct RequestMismatch {
+    mismatches: Vec<String>,
+}
+
+impl RequestMismatch {
+    /// Descriptions of each part of the request that didn't match
+    pub fn mismatches(&self) -> &[String] {
+        &self.mismatches
+    }
+}
+
+impl fmt::Display for RequestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the request didn't match: {}",
+            self.mismatches.join("; ")
+        )
+    }
+}
+
+impl Error for RequestMismatch {}
+
+fn sdk_body_bytes(body: &SdkBody) -> Option<&[u8]> {
+    body.bytes()
+}
 
 #[derive(Debug)]
-pub struct ValidateRequest {
-    pub expected: http::Request<SdkBody>,
-    pub actual: http::Request<SdkBody>,
+pub struct ValidateRequest<ReqBody = SdkBody> {
+    pub expected: http::Request<ReqBody>,
+    pub actual: http::Request<ReqBody>,
 }
 
 impl ValidateRequest {
@@ -156,7 +330,11 @@ impl ValidateRequest {
 /// - Respond to requests with a preloaded series of responses
 /// - Record requests for future examination
 ///
-/// The generic parameter `B` is the type of the response body.
+/// The generic parameter `B` is the type of the response body. The generic parameter `ReqBody` is
+/// the type of the request body, which defaults to [`SdkBody`](SdkBody). Request validation with
+/// [`assert_requests_match`](TestConnection::assert_requests_match) is only available for `SdkBody` requests.
+/// To check only parts of each request, create the connection with
+/// [`new_with_matchers`](TestConnection::new_with_matchers) instead.
 /// For more complex use cases, see [Tower Test](https://docs.rs/tower-test/0.4.0/tower_test/)
 /// Usage example:
 /// ```no_run
@@ -173,13 +351,13 @@ impl ValidateRequest {
 /// let client = aws_smithy_client::Client::from(conn);
 /// ```
 #[derive(Debug)]
-pub struct TestConnection<B> {
-    data: Arc<Mutex<ConnectVec<B>>>,
-    requests: Arc<Mutex<Vec<ValidateRequest>>>,
+pub struct TestConnection<B, ReqBody = SdkBody> {
+    data: Arc<Mutex<ExpectationVec<B, ReqBody>>>,
+    requests: Arc<Mutex<Vec<ValidateRequest<ReqBody>>>>,
 }
 
-// Need a clone impl that ignores `B`
-impl<B> Clone for TestConnection<B> {
+// Need a clone impl that ignores `B` and `ReqBody`
+impl<B, ReqBody> Clone for TestConnection<B, ReqBody> {
     fn clone(&self) -> Self {
         TestConnection {
             data: self.data.clone(),
@@ -188,8 +366,16 @@ impl<B> Clone for TestConnection<B> {
     }
 }
 
-impl<B> TestConnection<B> {
-    pub fn new(mut data: ConnectVec<B>) -> Self {
+impl<B, ReqBody> TestConnection<B, ReqBody> {
+    pub fn new(data: ConnectVec<B, ReqBody>) -> Self {
+        Self::from_expectations(
+            data.into_iter()
+                .map(|(request, response)| (Expectation::Request(request), response))
+                .collect(),
+        )
+    }
+
+    fn from_expectations(mut data: ExpectationVec<B, ReqBody>) -> Self {
         data.reverse();
         TestConnection {
             data: Arc::new(Mutex::new(data)),
@@ -197,9 +383,26 @@ impl<B> TestConnection<B> {
         }
     }
 
-    pub fn requests(&self) -> impl Deref<Target = Vec<ValidateRequest>> + '_ {
+    pub fn requests(&self) -> impl Deref<Target = Vec<ValidateRequest<ReqBody>>> + '_ {
         self.requests.lock().unwrap()
     }
+}
+
+impl<B> TestConnection<B> {
+    /// Create a connection that checks each request against a [`RequestMatcher`] as it is made
+    ///
+    /// A request that doesn't match fails with a [`ConnectorError`] caused by a
+    /// [`RequestMismatch`] describing the differences. Requests that were checked this way aren't
+    /// recorded in [`requests`](TestConnection::requests).
+    pub fn new_with_matchers(data: Vec<(RequestMatcher, http::Response<B>)>) -> Self {
+        Self::from_expectations(
+            data.into_iter()
+                .map(|(matcher, response)| {
+                    (Expectation::Matcher(matcher, sdk_body_bytes), response)
+                })
+                .collect(),
+        )
+    }
 
     pub fn assert_requests_match(&self, ignore_headers: &[HeaderName]) {
         for req in self.requests().iter() {
@@ -215,7 +418,7 @@ impl<B> TestConnection<B> {
     }
 }
 
-impl<B> tower::Service<http::Request<SdkBody>> for TestConnection<B>
+impl<B, ReqBody> tower::Service<http::Request<ReqBody>> for TestConnection<B, ReqBody>
 where
     SdkBody: From<B>,
 {
@@ -227,13 +430,23 @@ where
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, actual: Request<SdkBody>) -> Self::Future {
-        // todo: validate request
+    fn call(&mut self, actual: Request<ReqBody>) -> Self::Future {
         if let Some((expected, resp)) = self.data.lock().unwrap().pop() {
-            self.requests
-                .lock()
-                .unwrap()
-                .push(ValidateRequest { expected, actual });
+            match expected {
+                Expectation::Request(expected) => self
+                    .requests
+                    .lock()
+                    .unwrap()
+                    .push(ValidateRequest { expected, actual }),
+                Expectation::Matcher(matcher, body) => {
+                    if let Err(mismatch) = matcher.check_with_body(&actual, body(actual.body())) {
+                        return std::future::ready(Err(ConnectorError::other(
+                            mismatch.into(),
+                            None,
+                        )));
+                    }
+                }
+            }
             std::future::ready(Ok(resp.map(SdkBody::from)))
         } else {
             std::future::ready(Err(ConnectorError::other("No more data".into(), None)))
@@ -257,11 +470,16 @@ where
 #[cfg(test)]
 mod tests {
     use crate::bounds::SmithyConnector;
-    use crate::test_connection::{capture_request, never::NeverService, TestConnection};
+    use crate::test_connection::{
+        capture_request, never::NeverService, RequestMatcher, RequestMismatch, TestConnection,
+    };
     use crate::Client;
     use aws_smithy_http::body::SdkBody;
     use aws_smithy_http::result::ConnectorError;
+    use bytes::Bytes;
     use hyper::service::Service;
+    use std::error::Error;
+    use tower::ServiceExt;
 
     fn is_send_sync<T: Send + Sync>(_: T) {}
 
@@ -296,6 +514,82 @@ mod tests {
         is_a_connector(&tx)
     }
 
+    #[tokio::test]
+    async fn test_connection_generic_over_request_body() {
+        let conn = TestConnection::<hyper::Body, Bytes>::new(vec![(
+            http::Request::builder()
+                .uri("https://example.com/")
+                .body(Bytes::from_static(b"expected"))
+                .unwrap(),
+            http::Response::builder()
+                .status(200)
+                .body(hyper::Body::from("response"))
+                .unwrap(),
+        )]);
+
+        let request = http::Request::builder()
+            .uri("https://example.com/")
+            .body(Bytes::from_static(b"actual"))
+            .unwrap();
+        let response = conn.clone().oneshot(request).await.unwrap();
+        assert_eq!(200, response.status());
+
+        let requests = conn.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(&b"expected"[..], requests[0].expected.body());
+        assert_eq!(&b"actual"[..], requests[0].actual.body());
+    }
+
+    fn matcher_connection() -> TestConnection<&'static str> {
+        TestConnection::new_with_matchers(vec![(
+            RequestMatcher::new()
+                .method(http::Method::PUT)
+                .path("/bucket/key")
+                .header("x-amz-checksum-crc32", "i9aeUg==")
+                .body_contains(b"world"),
+            http::Response::builder().status(200).body("").unwrap(),
+        )])
+    }
+
+    fn request(checksum: Option<&str>) -> http::Request<SdkBody> {
+        let mut request = http::Request::builder()
+            .method("PUT")
+            .uri("https://example.com/bucket/key?x-id=PutObject");
+        if let Some(checksum) = checksum {
+            request = request.header("x-amz-checksum-crc32", checksum);
+        }
+        request.body(SdkBody::from("hello world")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn request_matcher_passes() {
+        let conn = matcher_connection();
+        let response = conn
+            .clone()
+            .oneshot(request(Some("i9aeUg==")))
+            .await
+            .unwrap();
+        assert_eq!(200, response.status());
+        conn.assert_requests_match(&[]);
+    }
+
+    #[tokio::test]
+    async fn request_matcher_missing_header() {
+        let err = matcher_connection()
+            .oneshot(request(None))
+            .await
+            .expect_err("the header is missing");
+        let mismatch = err
+            .source()
+            .unwrap()
+            .downcast_ref::<RequestMismatch>()
+            .unwrap();
+        assert_eq!(
+            &["expected header `x-amz-checksum-crc32` to be `i9aeUg==`, but it was missing"],
+            mismatch.mismatches()
+        );
+    }
+
     #[test]
     fn never_test() {
         is_a_connector(&NeverService::<
@@ -18,13 +18,16 @@ pub enum Error {
     InvalidHeadersLength,
     InvalidMessageLength,
     InvalidUtf8String,
-    MessageChecksumMismatch(u32, u32),
+    MessageChecksumMismatch(ChecksumMismatch),
     MessageTooLong,
     PayloadTooLong,
-    PreludeChecksumMismatch(u32, u32),
+    PreludeChecksumMismatch(ChecksumMismatch),
     TimestampValueTooLarge(DateTime),
     Marshalling(String),
     Unmarshalling(String),
+    /// The `:event-type` of an event isn't known to the unmarshaller, e.g. because the service
+    /// added a new event.
+    UnknownEventType(String),
 }
 
 impl StdError for Error {}
@@ -41,18 +44,10 @@ impl fmt::Display for Error {
             InvalidHeadersLength => write!(f, "invalid headers length"),
             InvalidMessageLength => write!(f, "invalid message length"),
             InvalidUtf8String => write!(f, "encountered invalid UTF-8 string"),
-            MessageChecksumMismatch(expected, actual) => write!(
-                f,
-                "message checksum 0x{:X} didn't match expected checksum 0x{:X}",
-                actual, expected
-            ),
+            MessageChecksumMismatch(mismatch) => write!(f, "message checksum {}", mismatch),
             MessageTooLong => write!(f, "message too long to fit in event stream frame"),
             PayloadTooLong => write!(f, "message payload too long to fit in event stream frame"),
-            PreludeChecksumMismatch(expected, actual) => write!(
-                f,
-                "prelude checksum 0x{:X} didn't match expected checksum 0x{:X}",
-                actual, expected
-            ),
+            PreludeChecksumMismatch(mismatch) => write!(f, "prelude checksum {}", mismatch),
             TimestampValueTooLarge(time) => write!(
                 f,
                 "timestamp value {:?} is too large to fit into an i64",
@@ -60,6 +55,69 @@ impl fmt::Display for Error {
             ),
             Marshalling(error) => write!(f, "failed to marshall message: {}", error),
             Unmarshalling(error) => write!(f, "failed to unmarshall message: {}", error),
+            UnknownEventType(event_type) => write!(f, "unrecognized :event-type: {}", event_type),
+        }
+    }
+}
+
+/// Details of a frame whose prelude or message CRC didn't match its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    expected_crc: u32,
+    actual_crc: u32,
+    total_length: u32,
+    frame_prefix: Vec<u8>,
+}
+
+impl ChecksumMismatch {
+    pub(crate) fn new(
+        expected_crc: u32,
+        actual_crc: u32,
+        total_length: u32,
+        frame_prefix: &[u8],
+    ) -> Self {
+        Self {
+            expected_crc,
+            actual_crc,
+            total_length,
+            frame_prefix: frame_prefix.into(),
+        }
+    }
+
+    /// Returns the CRC computed over the received bytes.
+    pub fn expected_crc(&self) -> u32 {
+        self.expected_crc
+    }
+
+    /// Returns the CRC that was sent in the frame.
+    pub fn actual_crc(&self) -> u32 {
+        self.actual_crc
+    }
+
+    /// Returns the total length of the frame, as read from its prelude.
+    pub fn total_length(&self) -> u32 {
+        self.total_length
+    }
+
+    /// Returns up to the first 64 bytes of the frame.
+    pub fn frame_prefix(&self) -> &[u8] {
+        &self.frame_prefix
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:X} didn't match expected checksum 0x{:X} (frame length: {} bytes, first {} bytes:",
+            self.actual_crc,
+            self.expected_crc,
+            self.total_length,
+            self.frame_prefix.len()
+        )?;
+        for byte in &self.frame_prefix {
+            write!(f, " {:02x}", byte)?;
         }
+        write!(f, ")")
     }
 }
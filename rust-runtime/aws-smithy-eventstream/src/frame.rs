@@ -7,19 +7,23 @@
 
 use crate::buf::count::CountBuf;
 use crate::buf::crc::{CrcBuf, CrcBufMut};
-use crate::error::Error;
+use crate::error::{ChecksumMismatch, Error};
 use crate::str_bytes::StrBytes;
 use bytes::{Buf, BufMut, Bytes};
 use std::convert::{TryFrom, TryInto};
 use std::error::Error as StdError;
 use std::fmt;
+use std::io::IoSlice;
 use std::mem::size_of;
+use std::task::{Context, Poll};
 
 const PRELUDE_LENGTH_BYTES: u32 = 3 * size_of::<u32>() as u32;
 const PRELUDE_LENGTH_BYTES_USIZE: usize = PRELUDE_LENGTH_BYTES as usize;
 const MESSAGE_CRC_LENGTH_BYTES: u32 = size_of::<u32>() as u32;
 const MAX_HEADER_NAME_LEN: usize = 255;
 const MIN_HEADER_LEN: usize = 2;
+/// Number of bytes at the start of a frame that are included in checksum mismatch errors
+const FRAME_PREFIX_LEN: usize = 64;
 
 pub type SignMessageError = Box<dyn StdError + Send + Sync + 'static>;
 
@@ -27,7 +31,21 @@ pub type SignMessageError = Box<dyn StdError + Send + Sync + 'static>;
 pub trait SignMessage: fmt::Debug {
     fn sign(&mut self, message: Message) -> Result<Message, SignMessageError>;
 
-    fn sign_empty(&mut self) -> Result<Message, SignMessageError>;
+    /// Signs the empty message that ends the event stream.
+    ///
+    /// Returns `None` if no end frame should be sent, which is appropriate when messages aren't
+    /// signed, since an unsigned stream is ended by closing the request body.
+    fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>>;
+
+    /// Returns `Poll::Ready(Ok(()))` once the signer is able to sign the next message.
+    ///
+    /// This is polled before each call to [`sign`](SignMessage::sign) or
+    /// [`sign_empty`](SignMessage::sign_empty), and lets signers do asynchronous work such as
+    /// refreshing credentials before they expire. Signers that are always ready don't need to
+    /// implement it.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), SignMessageError>> {
+        Poll::Ready(Ok(()))
+    }
 }
 
 /// Converts a Smithy modeled Event Stream type into a [`Message`](Message).
@@ -392,7 +410,7 @@ impl Message {
     }
 
     // Returns (total_len, header_len)
-    fn read_prelude_from<B: Buf>(mut buffer: B) -> Result<(u32, u32), Error> {
+    fn read_prelude_from<B: Buf>(mut buffer: B, frame_prefix: &[u8]) -> Result<(u32, u32), Error> {
         let mut crc_buffer = CrcBuf::new(&mut buffer);
 
         // If the buffer doesn't have the entire, then error
@@ -405,7 +423,12 @@ impl Message {
         let header_len = crc_buffer.get_u32();
         let (expected_crc, prelude_crc) = (crc_buffer.into_crc(), buffer.get_u32());
         if expected_crc != prelude_crc {
-            return Err(Error::PreludeChecksumMismatch(expected_crc, prelude_crc));
+            return Err(Error::PreludeChecksumMismatch(ChecksumMismatch::new(
+                expected_crc,
+                prelude_crc,
+                total_len,
+                frame_prefix,
+            )));
         }
         // The header length can be 0 or >= 2, but must fit within the frame size
         if header_len == 1 || header_len > max_header_len(total_len)? {
@@ -421,9 +444,13 @@ impl Message {
             return Err(Error::InvalidMessageLength);
         }
 
+        // Keep the start of the frame around to describe checksum mismatches
+        let (prefix, prefix_len) = frame_prefix(&buffer);
+        let frame_prefix = &prefix[..prefix_len];
+
         // Calculate a CRC as we go and read the prelude
         let mut crc_buffer = CrcBuf::new(&mut buffer);
-        let (total_len, header_len) = Self::read_prelude_from(&mut crc_buffer)?;
+        let (total_len, header_len) = Self::read_prelude_from(&mut crc_buffer, frame_prefix)?;
 
         // Verify we have the full frame before continuing
         let remaining_len = total_len
@@ -452,7 +479,12 @@ impl Message {
         let expected_crc = crc_buffer.into_crc();
         let message_crc = buffer.get_u32();
         if expected_crc != message_crc {
-            return Err(Error::MessageChecksumMismatch(expected_crc, message_crc));
+            return Err(Error::MessageChecksumMismatch(ChecksumMismatch::new(
+                expected_crc,
+                message_crc,
+                total_len,
+                frame_prefix,
+            )));
         }
 
         Ok(Message { headers, payload })
@@ -510,6 +542,20 @@ fn max_header_len(total_len: u32) -> Result<u32, Error> {
         .ok_or(Error::InvalidMessageLength)
 }
 
+/// Copies up to [`FRAME_PREFIX_LEN`] bytes from the start of `buffer` without consuming them
+fn frame_prefix<B: Buf>(buffer: &B) -> ([u8; FRAME_PREFIX_LEN], usize) {
+    let mut prefix = [0u8; FRAME_PREFIX_LEN];
+    let mut len = 0;
+    let mut chunks = [IoSlice::new(&[]); 8];
+    let count = buffer.chunks_vectored(&mut chunks);
+    for chunk in &chunks[..count] {
+        let copied = chunk.len().min(FRAME_PREFIX_LEN - len);
+        prefix[len..len + copied].copy_from_slice(&chunk[..copied]);
+        len += copied;
+    }
+    (prefix, len)
+}
+
 fn payload_len(total_len: u32, header_len: u32) -> Result<u32, Error> {
     total_len
         .checked_sub(
@@ -528,10 +574,10 @@ mod message_tests {
     use bytes::Bytes;
 
     macro_rules! read_message_expect_err {
-        ($bytes:expr, $err:pat) => {
+        ($bytes:expr, $err:pat $(if $guard:expr)?) => {
             let result = Message::read_from(&mut Bytes::from_static($bytes));
             assert!(
-                matches!(&result.as_ref(), &Err($err)),
+                matches!(&result.as_ref(), &Err($err) $(if $guard)?),
                 "Expected {}, got {:?}",
                 stringify!(Err($err)),
                 result
@@ -563,11 +609,13 @@ mod message_tests {
         );
         read_message_expect_err!(
             include_bytes!("../test_data/invalid_prelude_checksum"),
-            Error::PreludeChecksumMismatch(0x8BB495FB, 0xDEADBEEF)
+            Error::PreludeChecksumMismatch(mismatch)
+                if (mismatch.expected_crc(), mismatch.actual_crc()) == (0x8BB495FB, 0xDEADBEEF)
         );
         read_message_expect_err!(
             include_bytes!("../test_data/invalid_message_checksum"),
-            Error::MessageChecksumMismatch(0x01a05860, 0xDEADBEEF)
+            Error::MessageChecksumMismatch(mismatch)
+                if (mismatch.expected_crc(), mismatch.actual_crc()) == (0x01a05860, 0xDEADBEEF)
         );
         read_message_expect_err!(
             include_bytes!("../test_data/invalid_header_name_length_too_long"),
@@ -11,6 +11,7 @@ pub use http_body::Body as HttpBody;
 
 pub use hyper::body::Body;
 
+use aws_smithy_http::byte_stream::ByteStream;
 use bytes::Bytes;
 
 use crate::error::{BoxError, Error};
@@ -54,3 +55,41 @@ where
 {
     boxed(Body::from(body))
 }
+
+/// Convert a [`ByteStream`] into a [`BoxBody`], preserving its trailers.
+///
+/// The trailers produced by the [`BodyCallback`]s attached to the stream's [`SdkBody`], such as
+/// checksum callbacks, are sent as HTTP trailers once the whole body has been sent. Callbacks only
+/// produce their trailers after they have seen every byte of the body, so the trailers always
+/// describe what was actually sent. Note that hyper only sends trailers over HTTP/2.
+///
+/// [`BodyCallback`]: aws_smithy_http::callback::BodyCallback
+/// [`SdkBody`]: aws_smithy_http::body::SdkBody
+// Used in the codegen of streaming operation outputs.
+pub fn from_byte_stream(stream: ByteStream) -> BoxBody {
+    boxed(stream.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::callback::BodyCallback;
+
+    #[tokio::test]
+    async fn byte_stream_trailers_are_preserved() {
+        let mut body = SdkBody::from("hello world");
+        body.with_callback(Box::new(aws_smithy_checksums::try_new_checksum("crc32").unwrap()));
+        let mut body = from_byte_stream(ByteStream::new(body));
+
+        let mut sent = Vec::new();
+        while let Some(data) = body.data().await {
+            sent.extend_from_slice(&data.unwrap());
+        }
+        let trailers = body.trailers().await.unwrap().unwrap();
+
+        let mut expected = aws_smithy_checksums::try_new_checksum("crc32").unwrap();
+        expected.update(&sent).unwrap();
+        assert_eq!(expected.trailers().unwrap(), Some(trailers));
+    }
+}
@@ -0,0 +1,483 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Compression of response bodies.
+//!
+//! By default the server sends response bodies as the operation produced them. Applying a
+//! [`CompressionLayer`] to a [`Router`](crate::Router) compresses them with `gzip` or `deflate`
+//! when the request's `Accept-Encoding` header allows it, setting `Content-Encoding` and adding
+//! `Accept-Encoding` to `Vary`. A response is sent as it is if:
+//!
+//! - it already has a `Content-Encoding`;
+//! - its `Content-Type` is already compressed, e.g. images, video, or archives, or is an event
+//!   stream, whose messages have to reach the client as soon as they are sent;
+//! - its size is known and smaller than the configured minimum;
+//! - it describes specific bytes of its content: a `206 Partial Content` response, a response with
+//!   a `Content-Range`, or one with a strong `ETag`. Compressing them would change the bytes these
+//!   refer to, breaking e.g. the resumption of a [`ResumableResponse`](crate::resumable).
+//!
+//! ```rust,ignore
+//! let router = router.layer(CompressionLayer::new().with_min_size_bytes(4096));
+//! ```
+
+use crate::body::{boxed, BoxBody, HttpBody};
+use crate::error::BoxError;
+use bytes::Bytes;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, VARY};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// A [`tower::Layer`] that compresses response bodies for clients that accept it.
+///
+/// See the [module documentation](crate::compression) for details.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionLayer {
+    min_size: u64,
+}
+
+impl Default for CompressionLayer {
+    fn default() -> Self {
+        Self {
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+impl CompressionLayer {
+    /// Create a new layer that compresses response bodies of at least 1 KiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the size under which response bodies are sent uncompressed. Defaults to 1 KiB.
+    ///
+    /// Responses whose size isn't known up front, such as streaming responses, are always
+    /// compressed.
+    pub fn with_min_size_bytes(mut self, min_size_bytes: u64) -> Self {
+        self.min_size = min_size_bytes;
+        self
+    }
+}
+
+impl<S> Layer<S> for CompressionLayer {
+    type Service = CompressionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CompressionService {
+            inner,
+            min_size: self.min_size,
+        }
+    }
+}
+
+/// Middleware created by [`CompressionLayer`].
+#[derive(Debug, Clone)]
+pub struct CompressionService<S> {
+    inner: S,
+    min_size: u64,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for CompressionService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>, Error = Infallible>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        ResponseFuture {
+            encoding: Encoding::preferred(req.headers()),
+            min_size: self.min_size,
+            future: self.inner.call(req),
+        }
+    }
+}
+
+/// A content coding the layer can compress with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// The encoding with the highest quality value in the `Accept-Encoding` header, preferring
+    /// gzip in case of a tie. Returns `None` if the client doesn't accept either of them.
+    fn preferred(headers: &HeaderMap) -> Option<Self> {
+        let mut gzip = None;
+        let mut deflate = None;
+        let mut wildcard = None;
+        let codings = headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for coding in codings {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map(|quality| quality.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            if name.eq_ignore_ascii_case("gzip") {
+                gzip = Some(quality);
+            } else if name.eq_ignore_ascii_case("deflate") {
+                deflate = Some(quality);
+            } else if name == "*" {
+                wildcard = Some(quality);
+            }
+        }
+        // A wildcard only applies to the codings that weren't listed explicitly
+        let gzip = gzip.or(wildcard).unwrap_or(0.0);
+        let deflate = deflate.or(wildcard).unwrap_or(0.0);
+        if gzip <= 0.0 && deflate <= 0.0 {
+            None
+        } else if gzip >= deflate {
+            Some(Encoding::Gzip)
+        } else {
+            Some(Encoding::Deflate)
+        }
+    }
+}
+
+/// Returns `true` if the response refers to the exact bytes of its content, which compressing it
+/// would change.
+fn is_byte_exact<B>(response: &Response<B>) -> bool {
+    let headers = response.headers();
+    // Weak entity tags (`W/"..."`) only identify the content semantically
+    let strong_etag = matches!(headers.get(ETAG), Some(etag) if !etag.as_bytes().starts_with(b"W/"));
+    response.status() == StatusCode::PARTIAL_CONTENT || headers.contains_key(CONTENT_RANGE) || strong_etag
+}
+
+/// Returns `true` if compressing a body of this content type isn't worth it, or isn't desirable.
+fn is_incompressible(headers: &HeaderMap) -> bool {
+    let content_type = match headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+    {
+        Some(content_type) => content_type,
+        None => return false,
+    };
+    match (content_type.type_().as_str(), content_type.subtype().as_str()) {
+        ("image", "svg") => false,
+        ("image", _) | ("audio", _) | ("video", _) => true,
+        (
+            "application",
+            "gzip" | "x-gzip" | "zip" | "zstd" | "x-bzip2" | "x-7z-compressed" | "vnd.amazon.eventstream",
+        ) => true,
+        _ => false,
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response future for [`CompressionService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        future: F,
+        encoding: Option<Encoding>,
+        min_size: u64,
+    }
+}
+
+impl<F, ResBody> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, Infallible>>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<Response<BoxBody>, Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = futures_util::ready!(this.future.poll(cx))?;
+
+        let headers = response.headers();
+        let size = response.body().size_hint().exact().or_else(|| {
+            headers
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+        });
+        if headers.contains_key(CONTENT_ENCODING)
+            || is_incompressible(headers)
+            || is_byte_exact(&response)
+            || matches!(size, Some(size) if size < *this.min_size)
+        {
+            return Poll::Ready(Ok(response.map(boxed)));
+        }
+
+        let headers = response.headers_mut();
+        headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+        let encoding = match this.encoding {
+            Some(encoding) => *encoding,
+            None => return Poll::Ready(Ok(response.map(boxed))),
+        };
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+        headers.remove(CONTENT_LENGTH);
+        Poll::Ready(Ok(response.map(|body| boxed(CompressedBody::new(body, encoding)))))
+    }
+}
+
+#[derive(Debug)]
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            Encoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    /// Compresses `data`, returning whatever compressed output is ready so far.
+    fn encode(&mut self, data: &[u8]) -> std::io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write_all(data)?,
+            Encoder::Deflate(encoder) => encoder.write_all(data)?,
+        }
+        Ok(self.take_output())
+    }
+
+    /// Ends the compressed stream, returning the rest of the compressed output.
+    fn finish(&mut self) -> std::io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.try_finish()?,
+            Encoder::Deflate(encoder) => encoder.try_finish()?,
+        }
+        Ok(self.take_output())
+    }
+
+    fn take_output(&mut self) -> Bytes {
+        let output = match self {
+            Encoder::Gzip(encoder) => encoder.get_mut(),
+            Encoder::Deflate(encoder) => encoder.get_mut(),
+        };
+        Bytes::from(std::mem::take(output))
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response body compressed by a [`CompressionService`].
+    #[derive(Debug)]
+    pub struct CompressedBody<B> {
+        #[pin]
+        inner: B,
+        // `None` once the compressed stream has been finished.
+        encoder: Option<Encoder>,
+    }
+}
+
+impl<B> CompressedBody<B> {
+    fn new(inner: B, encoding: Encoding) -> Self {
+        Self {
+            inner,
+            encoder: Some(Encoder::new(encoding)),
+        }
+    }
+}
+
+impl<B> HttpBody for CompressedBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let mut this = self.project();
+        loop {
+            let encoder = match this.encoder.as_mut() {
+                Some(encoder) => encoder,
+                None => return Poll::Ready(None),
+            };
+            let output = match futures_util::ready!(this.inner.as_mut().poll_data(cx)) {
+                Some(Ok(data)) => encoder.encode(&data),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None => {
+                    let output = encoder.finish();
+                    *this.encoder = None;
+                    output
+                }
+            };
+            match output {
+                // The encoder buffers its input until it has enough of it to compress
+                Ok(output) if output.is_empty() => continue,
+                Ok(output) => return Poll::Ready(Some(Ok(output))),
+                Err(err) => return Poll::Ready(Some(Err(err.into()))),
+            }
+        }
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.encoder.is_none() && self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use flate2::read::GzDecoder;
+    use http::StatusCode;
+    use std::io::Read;
+    use tower::{service_fn, ServiceExt};
+
+    const BODY: &str = "{\"message\":\"hello world\"}";
+
+    /// Responds with `BODY` repeated `repeat` times, with the given content type.
+    fn service(
+        content_type: &'static str,
+        repeat: usize,
+    ) -> impl Service<Request<Body>, Response = Response<Body>, Error = Infallible, Future = impl Send> + Clone {
+        service_fn(move |_req: Request<Body>| async move {
+            let response = Response::builder()
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(BODY.repeat(repeat)))
+                .unwrap();
+            Ok(response)
+        })
+    }
+
+    fn request(accept_encoding: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder();
+        if let Some(accept_encoding) = accept_encoding {
+            request = request.header(ACCEPT_ENCODING, accept_encoding);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn gzip_capable_client_gets_compressed_response() {
+        let svc = CompressionLayer::new().layer(service("application/json", 100));
+
+        let res = svc
+            .oneshot(request(Some("br;q=1.0, gzip;q=0.8, *;q=0.1")))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("gzip", res.headers()[CONTENT_ENCODING]);
+        assert_eq!("accept-encoding", res.headers()[VARY]);
+        assert!(!res.headers().contains_key(CONTENT_LENGTH));
+
+        let compressed = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(compressed.len() < BODY.len() * 100);
+        let mut body = String::new();
+        GzDecoder::new(&compressed[..]).read_to_string(&mut body).unwrap();
+        assert_eq!(BODY.repeat(100), body);
+    }
+
+    #[tokio::test]
+    async fn client_without_compression_support_gets_plaintext() {
+        let svc = CompressionLayer::new().layer(service("application/json", 100));
+
+        for accept_encoding in [None, Some("identity"), Some("gzip;q=0, deflate;q=0")] {
+            let res = svc.clone().oneshot(request(accept_encoding)).await.unwrap();
+            assert!(!res.headers().contains_key(CONTENT_ENCODING));
+            assert_eq!("accept-encoding", res.headers()[VARY]);
+            let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            assert_eq!(BODY.repeat(100).as_bytes(), &body[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn incompressible_responses_are_sent_as_they_are() {
+        let small = CompressionLayer::new().layer(service("application/json", 1));
+        let res = small.oneshot(request(Some("gzip"))).await.unwrap();
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert!(!res.headers().contains_key(VARY));
+
+        let image = CompressionLayer::new().layer(service("image/png", 100));
+        let res = image.oneshot(request(Some("gzip"))).await.unwrap();
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(BODY.repeat(100).as_bytes(), &body[..]);
+    }
+
+    #[tokio::test]
+    async fn resumable_responses_are_sent_as_they_are() {
+        use crate::response::IntoResponse;
+        use crate::resumable::ResumableResponse;
+        use http::header::{CONTENT_RANGE, RANGE};
+
+        let content = BODY.repeat(100);
+        let svc = CompressionLayer::new().layer(service_fn(move |req: Request<Body>| {
+            let content = content.clone();
+            async move {
+                let response = ResumableResponse::new(content.len() as u64, "\"v1\"", |range| {
+                    Body::from(content[range.start as usize..range.end as usize].to_string())
+                })
+                .with_request_headers(req.headers())
+                .into_response();
+                Ok::<_, Infallible>(response)
+            }
+        }));
+
+        let mut range_request = request(Some("gzip"));
+        range_request
+            .headers_mut()
+            .insert(RANGE, HeaderValue::from_static("bytes=100-"));
+        let res = svc.clone().oneshot(range_request).await.unwrap();
+        assert_eq!(StatusCode::PARTIAL_CONTENT, res.status());
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert_eq!(
+            format!("bytes 100-{}/{}", BODY.len() * 100 - 1, BODY.len() * 100),
+            res.headers()[CONTENT_RANGE]
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&BODY.repeat(100).as_bytes()[100..], &body[..]);
+
+        // The full content is identified by a strong entity tag too
+        let res = svc.oneshot(request(Some("gzip"))).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn preferred_encoding() {
+        let preferred = |accept_encoding: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(accept_encoding).unwrap());
+            Encoding::preferred(&headers)
+        };
+        assert_eq!(Some(Encoding::Gzip), preferred("gzip, deflate"));
+        assert_eq!(Some(Encoding::Deflate), preferred("gzip;q=0.5, deflate"));
+        assert_eq!(Some(Encoding::Deflate), preferred("deflate"));
+        assert_eq!(Some(Encoding::Gzip), preferred("*"));
+        assert_eq!(Some(Encoding::Deflate), preferred("gzip;q=0, *"));
+        assert_eq!(None, preferred("br, identity"));
+    }
+}
@@ -11,9 +11,17 @@
 pub(crate) mod macros;
 
 pub mod body;
+pub mod compression;
 pub(crate) mod error;
 pub mod extension;
+pub mod memory_budget;
+pub mod metrics;
+pub mod request_body_limit;
+pub mod request_id;
+pub mod resumable;
 pub mod routing;
+#[cfg(unix)]
+pub mod unix;
 
 #[doc(hidden)]
 pub mod protocols;
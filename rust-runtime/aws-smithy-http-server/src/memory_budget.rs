@@ -0,0 +1,552 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Admission control based on the memory requests are expected to use.
+//!
+//! A [`MemoryBudgetLayer`] is configured with a global byte budget. When a request arrives, its
+//! declared `Content-Length` (or, for requests without one, a configurable estimate) is reserved
+//! against the budget. If the reservation does not fit, the request is rejected with
+//! `503 Service Unavailable` and a `Retry-After` header before it reaches the operation.
+//!
+//! The reservation is held until _both_ the request body has been consumed (or dropped) _and_ the
+//! response body has been sent. While the request body is being read, the bytes actually observed
+//! are compared against the reservation: if a request turns out to be larger than what was
+//! reserved, the reservation grows to match. If the budget cannot accommodate the growth, the
+//! request body yields a [`MemoryBudgetExceeded`] error, which the operation surfaces as its
+//! usual protocol error for a request that could not be read.
+//!
+//! Reservations are only tracked by the layer that made them: applying several
+//! `MemoryBudgetLayer`s to the same router makes each of them count every request.
+//!
+//! ```rust,ignore
+//! let router = router.layer(MemoryBudgetLayer::new(512 * 1024 * 1024));
+//! ```
+//!
+//! The budget limits the memory used by all requests together. To also limit the size of each
+//! request, apply a [`RequestBodyLimitLayer`] _after_ the `MemoryBudgetLayer`, so that it wraps it:
+//!
+//! ```rust,ignore
+//! let router = router
+//!     .layer(MemoryBudgetLayer::new(512 * 1024 * 1024))
+//!     .layer(RequestBodyLimitLayer::new(10 * 1024 * 1024));
+//! ```
+//!
+//! Both layers count the bytes of each request body, but only the `MemoryBudgetLayer` reserves
+//! memory for them, once per request. In this order, requests declaring a `Content-Length` over
+//! the limit are rejected with `413 Payload Too Large` without reserving anything, and a body
+//! crossing the limit fails in the [`LimitedBody`] before the [`BudgetedBody`] sees the bytes
+//! over it, so no reservation grows past the limit. In the opposite order, oversized requests hold
+//! a reservation for their declared length until their `413` response has been sent, and a body
+//! crossing the limit first grows its reservation, and may fail with [`MemoryBudgetExceeded`]
+//! rather than being rejected with a `413`.
+//!
+//! [`RequestBodyLimitLayer`]: crate::request_body_limit::RequestBodyLimitLayer
+//! [`LimitedBody`]: crate::request_body_limit::LimitedBody
+
+use crate::body::{boxed, BoxBody, HttpBody};
+use crate::error::BoxError;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+
+const DEFAULT_UNSIZED_ESTIMATE: u64 = 64 * 1024;
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Budget {
+    limit: u64,
+    reserved: AtomicU64,
+}
+
+impl Budget {
+    fn try_reserve(&self, bytes: u64) -> bool {
+        self.reserved
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |reserved| {
+                reserved.checked_add(bytes).filter(|total| *total <= self.limit)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, bytes: u64) {
+        self.reserved.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// Bytes reserved against a [`Budget`] on behalf of a single request, released on drop.
+#[derive(Debug)]
+struct Reservation {
+    budget: Arc<Budget>,
+    bytes: AtomicU64,
+}
+
+impl Reservation {
+    fn new(budget: &Arc<Budget>, bytes: u64) -> Option<Arc<Self>> {
+        if budget.try_reserve(bytes) {
+            Some(Arc::new(Self {
+                budget: budget.clone(),
+                bytes: AtomicU64::new(bytes),
+            }))
+        } else {
+            None
+        }
+    }
+
+    /// Grow the reservation so that it covers at least `total` bytes.
+    /// Returns `false` if the budget can't accommodate the growth.
+    fn grow_to(&self, total: u64) -> bool {
+        let current = self.bytes.load(Ordering::Acquire);
+        if total <= current {
+            return true;
+        }
+        if self.budget.try_reserve(total - current) {
+            self.bytes.store(total, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(*self.bytes.get_mut());
+    }
+}
+
+/// A [`tower::Layer`] that rejects requests when admitting them would exceed a memory budget.
+///
+/// See the [module documentation](crate::memory_budget) for details.
+#[derive(Debug, Clone)]
+pub struct MemoryBudgetLayer {
+    budget: Arc<Budget>,
+    unsized_estimate: u64,
+    retry_after: Duration,
+}
+
+impl MemoryBudgetLayer {
+    /// Create a new layer that allows at most `budget_bytes` to be reserved by in-flight requests.
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            budget: Arc::new(Budget {
+                limit: budget_bytes,
+                reserved: AtomicU64::new(0),
+            }),
+            unsized_estimate: DEFAULT_UNSIZED_ESTIMATE,
+            retry_after: DEFAULT_RETRY_AFTER,
+        }
+    }
+
+    /// Set the number of bytes initially reserved for requests without a `Content-Length`.
+    ///
+    /// The reservation is corrected as the request body is read. Defaults to 64 KiB.
+    pub fn with_unsized_estimate(mut self, estimate_bytes: u64) -> Self {
+        self.unsized_estimate = estimate_bytes;
+        self
+    }
+
+    /// Set the `Retry-After` sent with rejected requests. Defaults to one second.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// The number of bytes currently reserved by in-flight requests.
+    pub fn reserved_bytes(&self) -> u64 {
+        self.budget.reserved.load(Ordering::Acquire)
+    }
+}
+
+impl<S> Layer<S> for MemoryBudgetLayer {
+    type Service = MemoryBudget<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MemoryBudget {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Middleware created by [`MemoryBudgetLayer`].
+#[derive(Debug, Clone)]
+pub struct MemoryBudget<S> {
+    inner: S,
+    layer: MemoryBudgetLayer,
+}
+
+impl<S> MemoryBudget<S> {
+    fn reservation_for(&self, headers: &HeaderMap) -> Option<Arc<Reservation>> {
+        let declared = headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(self.layer.unsized_estimate);
+        Reservation::new(&self.layer.budget, declared)
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for MemoryBudget<S>
+where
+    S: Service<Request<BudgetedBody<B>>, Response = Response<ResBody>, Error = Infallible>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        match self.reservation_for(req.headers()) {
+            Some(reservation) => {
+                let req = req.map(|body| BudgetedBody {
+                    inner: body,
+                    reservation: Some(reservation.clone()),
+                    observed: 0,
+                });
+                ResponseFuture {
+                    state: State::Admitted {
+                        future: self.inner.call(req),
+                        reservation: Some(reservation),
+                    },
+                }
+            }
+            None => ResponseFuture {
+                state: State::Rejected {
+                    retry_after: self.layer.retry_after,
+                },
+            },
+        }
+    }
+}
+
+fn service_unavailable(retry_after: Duration) -> Response<BoxBody> {
+    // `Retry-After` only supports whole seconds, so round up.
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = Response::new(crate::body::empty());
+    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    response
+        .headers_mut()
+        .insert(http::header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
+pin_project_lite::pin_project! {
+    /// Response future for [`MemoryBudget`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        state: State<F>,
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = StateProj]
+    enum State<F> {
+        Admitted {
+            #[pin]
+            future: F,
+            reservation: Option<Arc<Reservation>>,
+        },
+        Rejected {
+            retry_after: Duration,
+        },
+    }
+}
+
+impl<F, ResBody> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, Infallible>>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<Response<BoxBody>, Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().state.project() {
+            StateProj::Admitted { future, reservation } => {
+                let response = futures_util::ready!(future.poll(cx))?;
+                let reservation = reservation.take();
+                Poll::Ready(Ok(response.map(|body| {
+                    boxed(ReleaseOnEnd {
+                        inner: body,
+                        reservation,
+                    })
+                })))
+            }
+            StateProj::Rejected { retry_after } => Poll::Ready(Ok(service_unavailable(*retry_after))),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Request body passed to services wrapped in a [`MemoryBudget`].
+    ///
+    /// Releases its share of the request's reservation once the body has been read to the end,
+    /// and yields a [`MemoryBudgetExceeded`] error if the body outgrows the memory budget.
+    #[derive(Debug)]
+    pub struct BudgetedBody<B> {
+        #[pin]
+        inner: B,
+        reservation: Option<Arc<Reservation>>,
+        observed: u64,
+    }
+}
+
+impl<B> HttpBody for BudgetedBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        match futures_util::ready!(this.inner.poll_data(cx)) {
+            Some(Ok(data)) => {
+                *this.observed += data.len() as u64;
+                if let Some(reservation) = this.reservation.as_ref() {
+                    if !reservation.grow_to(*this.observed) {
+                        this.reservation.take();
+                        return Poll::Ready(Some(Err(MemoryBudgetExceeded {
+                            observed: *this.observed,
+                        }
+                        .into())));
+                    }
+                }
+                Poll::Ready(Some(Ok(data)))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(err.into()))),
+            None => {
+                this.reservation.take();
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response body that releases its share of the request's reservation once it has been sent.
+    struct ReleaseOnEnd<B> {
+        #[pin]
+        inner: B,
+        reservation: Option<Arc<Reservation>>,
+    }
+}
+
+impl<B> HttpBody for ReleaseOnEnd<B>
+where
+    B: HttpBody<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let data = futures_util::ready!(this.inner.poll_data(cx));
+        if data.is_none() {
+            this.reservation.take();
+        }
+        Poll::Ready(data)
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let trailers = futures_util::ready!(this.inner.poll_trailers(cx));
+        this.reservation.take();
+        Poll::Ready(trailers)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Error yielded by a [`BudgetedBody`] when the request turns out to be larger than the memory
+/// budget can accommodate.
+#[derive(Debug)]
+pub struct MemoryBudgetExceeded {
+    observed: u64,
+}
+
+impl fmt::Display for MemoryBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request body exceeded the server's memory budget after {} bytes",
+            self.observed
+        )
+    }
+}
+
+impl std::error::Error for MemoryBudgetExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::request_body_limit::RequestBodyLimitLayer;
+    use crate::test_helpers::{chunked_request, read_body_service};
+    use tower::{ServiceBuilder, ServiceExt};
+
+    /// Reads the whole request body, then responds with `response_body`, or with
+    /// `400 Bad Request` if the request body could not be read.
+    fn echo_service(
+        response_body: impl Fn() -> Body + Clone + Send + 'static,
+    ) -> impl Service<Request<BudgetedBody<Body>>, Response = Response<Body>, Error = Infallible, Future = impl Send> + Clone
+    {
+        read_body_service(move |_| response_body())
+    }
+
+    fn sized_request(len: usize) -> Request<Body> {
+        Request::builder()
+            .header(http::header::CONTENT_LENGTH, len)
+            .body(Body::from(vec![b'x'; len]))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn admits_and_rejects_based_on_content_length() {
+        let layer = MemoryBudgetLayer::new(100);
+        let svc = layer.layer(echo_service(Body::empty));
+
+        let response = svc.clone().oneshot(sized_request(100)).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let response = svc.oneshot(sized_request(101)).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+        assert_eq!("1", response.headers()[http::header::RETRY_AFTER]);
+    }
+
+    #[tokio::test]
+    async fn reservation_is_held_until_the_response_body_completes() {
+        let layer = MemoryBudgetLayer::new(100).with_retry_after(Duration::from_millis(2500));
+        let (mut sender, body) = Body::channel();
+        let body = Arc::new(std::sync::Mutex::new(Some(body)));
+        let svc = layer.layer(echo_service(move || body.lock().unwrap().take().unwrap_or_default()));
+        let call = |req| svc.clone().oneshot(req);
+
+        let mut first = call(sized_request(60)).await.unwrap();
+        assert_eq!(StatusCode::OK, first.status());
+        // The request body has been consumed, but the response body is still streaming.
+        assert_eq!(60, layer.reserved_bytes());
+
+        let rejected = call(sized_request(60)).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, rejected.status());
+        assert_eq!("3", rejected.headers()[http::header::RETRY_AFTER]);
+
+        sender.send_data(Bytes::from_static(b"done")).await.unwrap();
+        drop(sender);
+        assert_eq!(
+            &b"done"[..],
+            &hyper::body::to_bytes(first.body_mut()).await.unwrap()[..]
+        );
+        assert_eq!(0, layer.reserved_bytes());
+
+        let admitted = call(sized_request(60)).await.unwrap();
+        assert_eq!(StatusCode::OK, admitted.status());
+    }
+
+    #[tokio::test]
+    async fn unsized_requests_correct_their_estimate() {
+        let layer = MemoryBudgetLayer::new(100).with_unsized_estimate(10);
+        let svc = layer.layer(echo_service(Body::empty));
+
+        let response = svc.oneshot(chunked_request(4, 20)).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        drop(response);
+        assert_eq!(0, layer.reserved_bytes());
+    }
+
+    #[tokio::test]
+    async fn unsized_request_overrunning_the_budget_is_aborted() {
+        let layer = MemoryBudgetLayer::new(100).with_unsized_estimate(10);
+        let svc = layer.layer(echo_service(Body::empty));
+
+        let response = svc.oneshot(chunked_request(4, 40)).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        let message = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            "request body exceeded the server's memory budget after 120 bytes",
+            std::str::from_utf8(&message).unwrap()
+        );
+        assert_eq!(0, layer.reserved_bytes());
+    }
+
+    #[tokio::test]
+    async fn stacked_with_a_body_limit_reserves_once_per_request() {
+        let layer = MemoryBudgetLayer::new(100).with_unsized_estimate(10);
+        let reserved_while_handled = Arc::new(AtomicU64::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(RequestBodyLimitLayer::new(50))
+            .layer(layer.clone())
+            .service(read_body_service({
+                let (layer, reserved_while_handled) = (layer.clone(), reserved_while_handled.clone());
+                move |_| {
+                    reserved_while_handled.store(layer.reserved_bytes(), Ordering::SeqCst);
+                    Body::empty()
+                }
+            }));
+        let call = |req| svc.clone().oneshot(req);
+
+        let response = call(sized_request(40)).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(40, reserved_while_handled.load(Ordering::SeqCst));
+        assert_eq!(40, layer.reserved_bytes());
+        drop(response);
+        assert_eq!(0, layer.reserved_bytes());
+
+        let response = call(chunked_request(2, 20)).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(40, reserved_while_handled.load(Ordering::SeqCst));
+        drop(response);
+        assert_eq!(0, layer.reserved_bytes());
+
+        // Over the limit: rejected by the body limit without reserving beyond it.
+        let response = call(sized_request(60)).await.unwrap();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, response.status());
+        assert_eq!(0, layer.reserved_bytes());
+
+        let response = call(chunked_request(3, 20)).await.unwrap();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, response.status());
+        drop(response);
+        assert_eq!(0, layer.reserved_bytes());
+    }
+
+    #[test]
+    fn traits() {
+        use crate::test_helpers::*;
+
+        assert_send::<BudgetedBody<Body>>();
+        assert_send::<ResponseFuture<std::future::Ready<Result<Response<Body>, Infallible>>>>();
+    }
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Request metrics.
+//!
+//! Applying a [`MetricsLayer`] to a [`Router`](crate::Router) reports, through a pluggable
+//! [`ServerMetrics`] implementation:
+//!
+//! - the number of requests currently being handled (the _inflight_ gauge);
+//! - a request count per operation and status code, and
+//! - a latency histogram per operation.
+//!
+//! Operations are identified by the [`OperationExtension`] set on the response; requests that were
+//! not routed to an operation (e.g. `404 Not Found` responses) have no operation.
+//!
+//! With the `metrics` feature enabled, [`MetricsFacade`] reports to the
+//! [`metrics`](https://docs.rs/metrics) crate, which can be exported to Prometheus with, for
+//! example, `metrics-exporter-prometheus`:
+//!
+//! ```rust,ignore
+//! let metrics = MetricsLayer::new(MetricsFacade);
+//! let router = router.layer(metrics.clone());
+//! ```
+//!
+//! [`MetricsLayer::inflight_requests`] reads the same counter that feeds the inflight gauge, so
+//! a server can use it to wait for inflight requests to drain before shutting down.
+
+use crate::extension::OperationExtension;
+use http::{Request, Response, StatusCode};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// Receives the metrics reported by a [`MetricsLayer`].
+pub trait ServerMetrics: Send + Sync + 'static {
+    /// Called with the number of inflight requests whenever a request starts or stops being
+    /// handled.
+    fn inflight_requests(&self, inflight: u64);
+
+    /// Called when the response to a request has been produced. The response body may still be
+    /// streaming.
+    ///
+    /// `operation` is `None` if the request was not routed to an operation.
+    fn request_completed(&self, operation: Option<&OperationExtension>, status: StatusCode, latency: Duration);
+}
+
+/// A [`tower::Layer`] that reports request metrics to a [`ServerMetrics`] implementation.
+///
+/// See the [module documentation](crate::metrics) for details.
+pub struct MetricsLayer<M> {
+    metrics: Arc<M>,
+    inflight: Arc<AtomicU64>,
+}
+
+impl<M> MetricsLayer<M> {
+    /// Create a new layer reporting to `metrics`.
+    pub fn new(metrics: M) -> Self {
+        Self {
+            metrics: Arc::new(metrics),
+            inflight: Default::default(),
+        }
+    }
+
+    /// Returns the number of requests currently being handled by services created by this layer
+    /// or its clones.
+    pub fn inflight_requests(&self) -> u64 {
+        self.inflight.load(Ordering::Acquire)
+    }
+}
+
+impl<M> Clone for MetricsLayer<M> {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+            inflight: self.inflight.clone(),
+        }
+    }
+}
+
+impl<M> fmt::Debug for MetricsLayer<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsLayer")
+            .field("inflight", &self.inflight)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, M> Layer<S> for MetricsLayer<M> {
+    type Service = Metrics<S, M>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Middleware created by [`MetricsLayer`].
+pub struct Metrics<S, M> {
+    inner: S,
+    layer: MetricsLayer<M>,
+}
+
+impl<S: Clone, M> Clone for Metrics<S, M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, M> fmt::Debug for Metrics<S, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("inner", &self.inner)
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+impl<S, M, B, ResBody> Service<Request<B>> for Metrics<S, M>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    M: ServerMetrics,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future, M>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let guard = InflightGuard::new(&self.layer);
+        ResponseFuture {
+            future: self.inner.call(req),
+            start: Instant::now(),
+            guard: Some(guard),
+        }
+    }
+}
+
+/// Counts a request as inflight until it is dropped.
+struct InflightGuard<M: ServerMetrics> {
+    metrics: Arc<M>,
+    inflight: Arc<AtomicU64>,
+}
+
+impl<M: ServerMetrics> InflightGuard<M> {
+    fn new(layer: &MetricsLayer<M>) -> Self {
+        let inflight = layer.inflight.fetch_add(1, Ordering::AcqRel) + 1;
+        layer.metrics.inflight_requests(inflight);
+        Self {
+            metrics: layer.metrics.clone(),
+            inflight: layer.inflight.clone(),
+        }
+    }
+}
+
+impl<M: ServerMetrics> Drop for InflightGuard<M> {
+    fn drop(&mut self) {
+        let inflight = self.inflight.fetch_sub(1, Ordering::AcqRel) - 1;
+        self.metrics.inflight_requests(inflight);
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response future for [`Metrics`].
+    pub struct ResponseFuture<F, M: ServerMetrics> {
+        #[pin]
+        future: F,
+        start: Instant,
+        // Dropped once the response has been produced, or if the request is cancelled.
+        guard: Option<InflightGuard<M>>,
+    }
+}
+
+impl<F, M, ResBody, E> Future for ResponseFuture<F, M>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    M: ServerMetrics,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures_util::ready!(this.future.poll(cx));
+        if let Some(guard) = this.guard.take() {
+            if let Ok(response) = &result {
+                guard.metrics.request_completed(
+                    response.extensions().get::<OperationExtension>(),
+                    response.status(),
+                    this.start.elapsed(),
+                );
+            }
+        }
+        Poll::Ready(result)
+    }
+}
+
+/// A [`ServerMetrics`] implementation reporting to the [`metrics`](https://docs.rs/metrics) crate.
+///
+/// The following metrics are reported:
+///
+/// - `smithy_server_inflight_requests`: a gauge of the number of inflight requests;
+/// - `smithy_server_requests_total`: a counter of completed requests, labeled with `operation`
+///   (`<namespace>#<operation_name>`, or `unknown`) and `status`;
+/// - `smithy_server_request_duration_seconds`: a histogram of request latencies, labeled with
+///   `operation`.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsFacade;
+
+#[cfg(feature = "metrics")]
+impl ServerMetrics for MetricsFacade {
+    fn inflight_requests(&self, inflight: u64) {
+        ::metrics::gauge!("smithy_server_inflight_requests", inflight as f64);
+    }
+
+    fn request_completed(&self, operation: Option<&OperationExtension>, status: StatusCode, latency: Duration) {
+        let operation = operation.map_or_else(|| "unknown".to_string(), OperationExtension::operation);
+        ::metrics::increment_counter!(
+            "smithy_server_requests_total",
+            "operation" => operation.clone(),
+            "status" => status.as_str().to_string()
+        );
+        ::metrics::histogram!(
+            "smithy_server_request_duration_seconds",
+            latency,
+            "operation" => operation
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+    use tower::{service_fn, ServiceExt};
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        inflight: Mutex<Vec<u64>>,
+        completed: Mutex<Vec<(Option<String>, StatusCode)>>,
+    }
+
+    impl ServerMetrics for Arc<RecordingMetrics> {
+        fn inflight_requests(&self, inflight: u64) {
+            self.inflight.lock().unwrap().push(inflight);
+        }
+
+        fn request_completed(&self, operation: Option<&OperationExtension>, status: StatusCode, _latency: Duration) {
+            self.completed
+                .lock()
+                .unwrap()
+                .push((operation.map(OperationExtension::operation), status));
+        }
+    }
+
+    #[tokio::test]
+    async fn request_increments_and_decrements_the_inflight_gauge() {
+        let recorded = Arc::new(RecordingMetrics::default());
+        let layer = MetricsLayer::new(recorded.clone());
+        let observer = layer.clone();
+        let svc = layer.layer(service_fn(move |_req: Request<Body>| {
+            let inflight = observer.inflight_requests();
+            async move {
+                let mut response = Response::new(Body::from(inflight.to_string()));
+                response
+                    .extensions_mut()
+                    .insert(OperationExtension::new("com.example", "GetThing"));
+                Ok::<_, Infallible>(response)
+            }
+        }));
+
+        let res = svc.oneshot(Request::new(Body::empty())).await.unwrap();
+        assert_eq!(&b"1"[..], &hyper::body::to_bytes(res.into_body()).await.unwrap()[..]);
+        assert_eq!(vec![1, 0], *recorded.inflight.lock().unwrap());
+        assert_eq!(0, layer.inflight_requests());
+        assert_eq!(
+            vec![(Some("com.example#GetThing".to_string()), StatusCode::OK)],
+            *recorded.completed.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_request_is_no_longer_inflight() {
+        let recorded = Arc::new(RecordingMetrics::default());
+        let layer = MetricsLayer::new(recorded.clone());
+        let mut svc = layer.layer(service_fn(|_req: Request<Body>| {
+            futures_util::future::pending::<Result<Response<Body>, Infallible>>()
+        }));
+
+        let future = svc.call(Request::new(Body::empty()));
+        assert_eq!(1, layer.inflight_requests());
+        drop(future);
+        assert_eq!(0, layer.inflight_requests());
+        assert_eq!(vec![1, 0], *recorded.inflight.lock().unwrap());
+        assert!(recorded.completed.lock().unwrap().is_empty());
+    }
+}
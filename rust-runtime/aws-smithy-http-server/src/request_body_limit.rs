@@ -0,0 +1,286 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Limits on the size of request bodies.
+//!
+//! By default the server accepts request bodies of any size. Applying a [`RequestBodyLimitLayer`]
+//! to a [`Router`](crate::Router) rejects requests whose body exceeds the limit with
+//! `413 Payload Too Large`:
+//!
+//! - requests declaring a `Content-Length` larger than the limit are rejected before the
+//!   operation handler runs;
+//! - for requests without a `Content-Length` (e.g. chunked requests), the request body yields a
+//!   [`LengthLimitExceeded`] error as soon as the limit is crossed. Whatever response the handler
+//!   produces after that is replaced with a `413 Payload Too Large`.
+//!
+//! ```rust,ignore
+//! let router = router.layer(RequestBodyLimitLayer::new(10 * 1024 * 1024));
+//! ```
+
+use crate::body::{boxed, BoxBody, HttpBody};
+use crate::error::BoxError;
+use bytes::Bytes;
+use http::{HeaderMap, Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// A [`tower::Layer`] that rejects requests with bodies larger than a limit.
+///
+/// See the [module documentation](crate::request_body_limit) for details.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestBodyLimitLayer {
+    limit: usize,
+}
+
+impl RequestBodyLimitLayer {
+    /// Create a new layer that rejects request bodies larger than `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+impl<S> Layer<S> for RequestBodyLimitLayer {
+    type Service = RequestBodyLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestBodyLimit {
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Middleware created by [`RequestBodyLimitLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestBodyLimit<S> {
+    inner: S,
+    limit: usize,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RequestBodyLimit<S>
+where
+    S: Service<Request<LimitedBody<B>>, Response = Response<ResBody>, Error = Infallible>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let declared = req
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if matches!(declared, Some(declared) if declared > self.limit as u64) {
+            return ResponseFuture {
+                future: None,
+                exceeded: Arc::new(AtomicBool::new(true)),
+            };
+        }
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let limit = self.limit;
+        let req = req.map(|body| LimitedBody {
+            inner: body,
+            remaining: limit,
+            limit,
+            exceeded: exceeded.clone(),
+        });
+        ResponseFuture {
+            future: Some(self.inner.call(req)),
+            exceeded,
+        }
+    }
+}
+
+fn payload_too_large() -> Response<BoxBody> {
+    let mut response = Response::new(crate::body::empty());
+    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    response
+}
+
+pin_project_lite::pin_project! {
+    /// Response future for [`RequestBodyLimit`].
+    pub struct ResponseFuture<F> {
+        // `None` if the request was rejected up front.
+        #[pin]
+        future: Option<F>,
+        exceeded: Arc<AtomicBool>,
+    }
+}
+
+impl<F, ResBody> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, Infallible>>,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Output = Result<Response<BoxBody>, Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = match this.future.as_pin_mut() {
+            Some(future) => futures_util::ready!(future.poll(cx))?,
+            None => return Poll::Ready(Ok(payload_too_large())),
+        };
+        if this.exceeded.load(Ordering::Acquire) {
+            Poll::Ready(Ok(payload_too_large()))
+        } else {
+            Poll::Ready(Ok(response.map(boxed)))
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Request body passed to services wrapped in a [`RequestBodyLimit`].
+    ///
+    /// Yields a [`LengthLimitExceeded`] error once more than the limit has been read.
+    #[derive(Debug)]
+    pub struct LimitedBody<B> {
+        #[pin]
+        inner: B,
+        remaining: usize,
+        limit: usize,
+        exceeded: Arc<AtomicBool>,
+    }
+}
+
+impl<B> HttpBody for LimitedBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        match futures_util::ready!(this.inner.poll_data(cx)) {
+            Some(Ok(data)) => match this.remaining.checked_sub(data.len()) {
+                Some(remaining) => {
+                    *this.remaining = remaining;
+                    Poll::Ready(Some(Ok(data)))
+                }
+                None => {
+                    this.exceeded.store(true, Ordering::Release);
+                    Poll::Ready(Some(Err(LengthLimitExceeded { limit: *this.limit }.into())))
+                }
+            },
+            Some(Err(err)) => Poll::Ready(Some(Err(err.into()))),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Error yielded by a [`LimitedBody`] when the request body is larger than the limit.
+#[derive(Debug)]
+pub struct LengthLimitExceeded {
+    limit: usize,
+}
+
+impl fmt::Display for LengthLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body exceeded the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for LengthLimitExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::test_helpers::{chunked_request, read_body_service};
+    use std::sync::atomic::AtomicUsize;
+    use tower::ServiceExt;
+
+    /// Echoes the request body, or responds with `400 Bad Request` if it could not be read.
+    /// Counts how many request bodies it has read.
+    fn echo_service(
+        calls: Arc<AtomicUsize>,
+    ) -> impl Service<Request<LimitedBody<Body>>, Response = Response<Body>, Error = Infallible, Future = impl Send> + Clone
+    {
+        read_body_service(move |body| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Body::from(body)
+        })
+    }
+
+    #[tokio::test]
+    async fn declared_length_over_the_limit_is_rejected_before_the_handler() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = RequestBodyLimitLayer::new(10).layer(echo_service(calls.clone()));
+
+        let req = Request::builder()
+            .header(http::header::CONTENT_LENGTH, 11)
+            .body(Body::from("hello world"))
+            .unwrap();
+        let res = svc.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+
+        let req = Request::builder()
+            .header(http::header::CONTENT_LENGTH, 10)
+            .body(Body::from("helloworld"))
+            .unwrap();
+        let mut res = svc.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            &b"helloworld"[..],
+            &hyper::body::to_bytes(res.body_mut()).await.unwrap()[..]
+        );
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn streaming_body_over_the_limit_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = RequestBodyLimitLayer::new(100).layer(echo_service(calls));
+
+        let res = svc.clone().oneshot(chunked_request(4, 25)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let res = svc.oneshot(chunked_request(5, 25)).await.unwrap();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+    }
+
+    #[tokio::test]
+    async fn limited_body_yields_an_error() {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let body = LimitedBody {
+            inner: Body::from("hello world"),
+            remaining: 5,
+            limit: 5,
+            exceeded: exceeded.clone(),
+        };
+        let err = hyper::body::to_bytes(body).await.unwrap_err();
+        assert_eq!("request body exceeded the limit of 5 bytes", err.to_string());
+        assert!(exceeded.load(Ordering::SeqCst));
+    }
+}
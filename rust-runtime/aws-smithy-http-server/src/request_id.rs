@@ -0,0 +1,249 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Request ids, to correlate the logs of a request with those of its client.
+//!
+//! Applying a [`RequestIdLayer`] to a [`Router`](crate::Router) reads the correlation headers of
+//! each request (by default `x-amzn-requestid` and `amz-sdk-invocation-id`) and:
+//!
+//! - stores them in a [`RequestIds`] request extension, which operation handlers can extract with
+//!   [`Extension<RequestIds>`](crate::Extension);
+//! - handles the request within a `request` [tracing span](tracing::Span) carrying a
+//!   `request_id` field, and a `correlation_ids` field listing every correlation header that was
+//!   sent, so that the logs emitted while handling the request can be correlated.
+//!
+//! The request id is the value of the first correlation header the request has, in the order
+//! they were configured. Requests without any of them are given a generated id.
+//!
+//! ```rust,ignore
+//! let router = router.layer(RequestIdLayer::new());
+//! ```
+
+use http::header::HeaderName;
+use http::Request;
+use std::fmt;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+
+const DEFAULT_HEADERS: [&str; 2] = ["x-amzn-requestid", "amz-sdk-invocation-id"];
+
+/// A [`tower::Layer`] that extracts the request id of each request and records it in a tracing
+/// span.
+///
+/// See the [module documentation](crate::request_id) for details.
+#[derive(Debug, Clone)]
+pub struct RequestIdLayer {
+    headers: Vec<HeaderName>,
+}
+
+impl Default for RequestIdLayer {
+    fn default() -> Self {
+        Self {
+            headers: DEFAULT_HEADERS
+                .iter()
+                .map(|name| HeaderName::from_static(name))
+                .collect(),
+        }
+    }
+}
+
+impl RequestIdLayer {
+    /// Create a new layer reading the `x-amzn-requestid` and `amz-sdk-invocation-id` headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the correlation headers `headers` instead of the default ones.
+    ///
+    /// The request id is taken from the first of them that a request has.
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.headers = headers.into_iter().collect();
+        self
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestId<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestId {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Middleware created by [`RequestIdLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestId<S> {
+    inner: S,
+    layer: RequestIdLayer,
+}
+
+impl<S, B> Service<Request<B>> for RequestId<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let request_ids = RequestIds::from_request(&self.layer.headers, &req);
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_ids.request_id(),
+            correlation_ids = ?request_ids.correlation_ids,
+        );
+        req.extensions_mut().insert(request_ids);
+        let _entered = span.enter();
+        self.inner.call(req).instrument(span.clone())
+    }
+}
+
+/// Request extension holding the request id and correlation headers of a request.
+///
+/// Set by a [`RequestIdLayer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestIds {
+    request_id: String,
+    generated: bool,
+    correlation_ids: Vec<(HeaderName, String)>,
+}
+
+impl RequestIds {
+    fn from_request<B>(headers: &[HeaderName], req: &Request<B>) -> Self {
+        let correlation_ids: Vec<_> = headers
+            .iter()
+            .filter_map(|name| {
+                let value = req.headers().get(name)?.to_str().ok()?;
+                Some((name.clone(), value.to_owned()))
+            })
+            .collect();
+        match correlation_ids.first() {
+            Some((_, request_id)) => Self {
+                request_id: request_id.clone(),
+                generated: false,
+                correlation_ids,
+            },
+            None => Self {
+                request_id: generate_request_id(),
+                generated: true,
+                correlation_ids,
+            },
+        }
+    }
+
+    /// The id of the request: the value of the first correlation header the request had, or a
+    /// generated id if it had none.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Returns `true` if the request had no correlation header, and its id was generated.
+    pub fn is_generated(&self) -> bool {
+        self.generated
+    }
+
+    /// The value of the correlation header `name`, if the request had it.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.correlation_ids
+            .iter()
+            .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl fmt::Display for RequestIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.request_id)
+    }
+}
+
+/// Generates a random (version 4) UUID.
+fn generate_request_id() -> String {
+    let high = fastrand::u64(..);
+    let low = fastrand::u64(..);
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0x0fff,
+        (low >> 48) & 0x3fff | 0x8000,
+        low & 0xffff_ffff_ffff,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use http::Response;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+    use tracing_test::traced_test;
+
+    /// Logs a message and responds with the request id it was given.
+    fn service() -> impl Service<Request<Body>, Response = Response<Body>, Error = Infallible> {
+        service_fn(|req: Request<Body>| async move {
+            tracing::info!("handling request");
+            let request_ids = req.extensions().get::<RequestIds>().unwrap();
+            Ok(Response::new(Body::from(request_ids.request_id().to_owned())))
+        })
+    }
+
+    async fn request_id(req: Request<Body>) -> String {
+        let res = RequestIdLayer::new().layer(service()).oneshot(req).await.unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn incoming_request_id_is_recorded() {
+        let req = Request::builder()
+            .header("amz-sdk-invocation-id", "invocation-1")
+            .header("x-amzn-requestid", "request-1")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!("request-1", request_id(req).await);
+        assert!(logs_contain("request_id=request-1"));
+        assert!(logs_contain("\"amz-sdk-invocation-id\", \"invocation-1\""));
+        assert!(logs_contain("handling request"));
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn request_id_is_generated_when_absent() {
+        let id = request_id(Request::new(Body::empty())).await;
+
+        assert_eq!(36, id.len());
+        assert_eq!(Some('4'), id.chars().nth(14));
+        assert!(logs_contain(&format!("request_id={}", id)));
+    }
+
+    #[test]
+    fn configured_headers() {
+        let headers = [HeaderName::from_static("x-correlation-id")];
+        let req = Request::builder()
+            .header("x-amzn-requestid", "request-1")
+            .header("x-correlation-id", "correlation-1")
+            .body(())
+            .unwrap();
+
+        let request_ids = RequestIds::from_request(&headers, &req);
+        assert_eq!("correlation-1", request_ids.request_id());
+        assert!(!request_ids.is_generated());
+        assert_eq!(Some("correlation-1"), request_ids.get("X-Correlation-Id"));
+        assert_eq!(None, request_ids.get("x-amzn-requestid"));
+    }
+}
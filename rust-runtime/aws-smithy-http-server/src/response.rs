@@ -44,3 +44,17 @@ pub trait IntoResponse {
     /// Create a response.
     fn into_response(self) -> Response;
 }
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for http::StatusCode {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(crate::body::empty());
+        *response.status_mut() = self;
+        response
+    }
+}
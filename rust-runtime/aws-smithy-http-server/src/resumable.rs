@@ -0,0 +1,246 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Resumable downloads.
+//!
+//! A [`ResumableResponse`] serves a streaming response that clients can resume after a dropped
+//! connection by sending a [`Range`] request. The handler only provides the total length of the
+//! content, an [entity tag] identifying its version, and a closure producing the body for an
+//! arbitrary byte range; the `Range` and [`If-Range`] request headers are handled for it:
+//!
+//! - without a satisfiable `Range`, or when `If-Range` doesn't match the entity tag, the full
+//!   content is returned with `200 OK`;
+//! - a single satisfiable range is returned with `206 Partial Content` and a `Content-Range`
+//!   header;
+//! - an unsatisfiable range, or a request for multiple ranges, is rejected with
+//!   `416 Range Not Satisfiable`. Multipart responses are not supported.
+//!
+//! `If-Range` is only honored when it carries a strong entity tag; dates and weak entity tags
+//! never match, so they result in the full content being sent.
+//!
+//! ```rust,ignore
+//! ResumableResponse::new(export.len(), export.etag(), |range| export.body_for(range))
+//!     .with_request_headers(request.headers())
+//!     .into_response()
+//! ```
+//!
+//! [`Range`]: https://httpwg.org/specs/rfc9110.html#field.range
+//! [`If-Range`]: https://httpwg.org/specs/rfc9110.html#field.if-range
+//! [entity tag]: https://httpwg.org/specs/rfc9110.html#field.etag
+
+use crate::body::{boxed, HttpBody};
+use crate::error::BoxError;
+use crate::response::{IntoResponse, Response};
+use bytes::Bytes;
+use http::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use std::ops::Range;
+
+/// A response to a download that can be resumed with a `Range` request.
+///
+/// See the [module documentation](crate::resumable) for details.
+#[derive(Debug)]
+pub struct ResumableResponse<F> {
+    total_length: u64,
+    etag: String,
+    range: Option<HeaderValue>,
+    if_range: Option<HeaderValue>,
+    body_for_range: F,
+}
+
+impl<F, B> ResumableResponse<F>
+where
+    F: FnOnce(Range<u64>) -> B,
+{
+    /// Create a response for content of `total_length` bytes, identified by the entity tag `etag`
+    /// (including its quotes, e.g. `"v1"`).
+    ///
+    /// `body_for_range` is called exactly once, with the range of bytes the body must contain.
+    pub fn new(total_length: u64, etag: impl Into<String>, body_for_range: F) -> Self {
+        Self {
+            total_length,
+            etag: etag.into(),
+            range: None,
+            if_range: None,
+            body_for_range,
+        }
+    }
+
+    /// Use the `Range` and `If-Range` headers of the request being responded to.
+    pub fn with_request_headers(mut self, headers: &HeaderMap) -> Self {
+        self.range = headers.get(RANGE).cloned();
+        self.if_range = headers.get(IF_RANGE).cloned();
+        self
+    }
+
+    fn requested_range(&self) -> Result<Option<Range<u64>>, Unsatisfiable> {
+        let range = match &self.range {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        if let Some(if_range) = &self.if_range {
+            // Weak entity tags start with `W/`, and never equal the strong tag they are compared
+            // to; neither do dates.
+            if if_range.as_bytes() != self.etag.as_bytes() || self.etag.starts_with("W/") {
+                return Ok(None);
+            }
+        }
+        match range.to_str() {
+            Ok(range) => parse_range(range, self.total_length),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// The requested range can't be served.
+#[derive(Debug, PartialEq, Eq)]
+struct Unsatisfiable;
+
+/// Parses a `Range` header value, returning `None` if it should be ignored.
+fn parse_range(value: &str, total_length: u64) -> Result<Option<Range<u64>>, Unsatisfiable> {
+    let spec = match value.trim().strip_prefix("bytes=") {
+        Some(spec) => spec.trim(),
+        // Unknown range units must be ignored.
+        None => return Ok(None),
+    };
+    if spec.contains(',') {
+        return Err(Unsatisfiable);
+    }
+    let (first, last) = match spec.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => return Ok(None),
+    };
+    let parse = |value: &str| value.parse::<u64>().map_err(|_| ());
+    let range = match (first, last) {
+        ("", "") => return Ok(None),
+        // `bytes=-500` is the last 500 bytes.
+        ("", suffix) => match parse(suffix) {
+            Ok(0) => return Err(Unsatisfiable),
+            Ok(suffix) => total_length.saturating_sub(suffix)..total_length,
+            Err(_) => return Ok(None),
+        },
+        (first, "") => match parse(first) {
+            Ok(first) => first..total_length,
+            Err(_) => return Ok(None),
+        },
+        (first, last) => match (parse(first), parse(last)) {
+            (Ok(first), Ok(last)) if first <= last => first..total_length.min(last.saturating_add(1)),
+            _ => return Ok(None),
+        },
+    };
+    if range.start >= total_length {
+        return Err(Unsatisfiable);
+    }
+    Ok(Some(range))
+}
+
+impl<F, B> IntoResponse for ResumableResponse<F>
+where
+    F: FnOnce(Range<u64>) -> B,
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    fn into_response(self) -> Response {
+        let requested = self.requested_range();
+        let mut builder = http::Response::builder()
+            .header(ACCEPT_RANGES, "bytes")
+            .header(ETAG, &self.etag);
+        let response = match requested {
+            Ok(range) => {
+                if let Some(range) = &range {
+                    builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                        CONTENT_RANGE,
+                        format!("bytes {}-{}/{}", range.start, range.end - 1, self.total_length),
+                    );
+                }
+                let range = range.unwrap_or(0..self.total_length);
+                builder
+                    .header(CONTENT_LENGTH, range.end - range.start)
+                    .body(boxed((self.body_for_range)(range)))
+            }
+            Err(Unsatisfiable) => builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{}", self.total_length))
+                .body(crate::body::empty()),
+        };
+        response.expect("valid resumable response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+
+    const CONTENT: &[u8] = b"0123456789";
+    const ETAG_V1: &str = "\"v1\"";
+
+    fn respond(headers: &[(http::header::HeaderName, &str)]) -> Response {
+        let mut request_headers = HeaderMap::new();
+        for (name, value) in headers {
+            request_headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        ResumableResponse::new(CONTENT.len() as u64, ETAG_V1, |range: Range<u64>| {
+            Body::from(&CONTENT[range.start as usize..range.end as usize])
+        })
+        .with_request_headers(&request_headers)
+        .into_response()
+    }
+
+    async fn body_of(response: Response) -> Bytes {
+        hyper::body::to_bytes(response.into_body()).await.unwrap()
+    }
+
+    #[test]
+    fn range_parsing() {
+        assert_eq!(Ok(Some(2..5)), parse_range("bytes=2-4", 10));
+        assert_eq!(Ok(Some(2..10)), parse_range("bytes=2-", 10));
+        assert_eq!(Ok(Some(2..10)), parse_range("bytes=2-100", 10));
+        assert_eq!(Ok(Some(7..10)), parse_range("bytes=-3", 10));
+        assert_eq!(Ok(Some(0..10)), parse_range("bytes=-30", 10));
+        assert_eq!(Err(Unsatisfiable), parse_range("bytes=10-", 10));
+        assert_eq!(Err(Unsatisfiable), parse_range("bytes=-0", 10));
+        assert_eq!(Err(Unsatisfiable), parse_range("bytes=0-1,4-5", 10));
+        assert_eq!(Ok(None), parse_range("items=0-1", 10));
+        assert_eq!(Ok(None), parse_range("bytes=4-2", 10));
+        assert_eq!(Ok(None), parse_range("bytes=a-b", 10));
+    }
+
+    #[tokio::test]
+    async fn full_download() {
+        let response = respond(&[]);
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("10", response.headers()[CONTENT_LENGTH]);
+        assert_eq!(ETAG_V1, response.headers()[ETAG]);
+        assert_eq!("bytes", response.headers()[ACCEPT_RANGES]);
+        assert!(response.headers().get(CONTENT_RANGE).is_none());
+        assert_eq!(CONTENT, &body_of(response).await[..]);
+    }
+
+    #[tokio::test]
+    async fn satisfiable_range() {
+        let response = respond(&[(RANGE, "bytes=4-"), (IF_RANGE, ETAG_V1)]);
+        assert_eq!(StatusCode::PARTIAL_CONTENT, response.status());
+        assert_eq!("bytes 4-9/10", response.headers()[CONTENT_RANGE]);
+        assert_eq!("6", response.headers()[CONTENT_LENGTH]);
+        assert_eq!(&b"456789"[..], &body_of(response).await[..]);
+    }
+
+    #[tokio::test]
+    async fn unsatisfiable_range() {
+        let response = respond(&[(RANGE, "bytes=20-30")]);
+        assert_eq!(StatusCode::RANGE_NOT_SATISFIABLE, response.status());
+        assert_eq!("bytes */10", response.headers()[CONTENT_RANGE]);
+        assert!(body_of(response).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn if_range_mismatch_sends_full_content() {
+        let response = respond(&[(RANGE, "bytes=4-"), (IF_RANGE, "\"v0\"")]);
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(response.headers().get(CONTENT_RANGE).is_none());
+        assert_eq!(CONTENT, &body_of(response).await[..]);
+    }
+}
@@ -17,6 +17,7 @@ use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
 use http::{Request, Response, StatusCode};
 use std::{
     convert::Infallible,
+    fmt,
     task::{Context, Poll},
 };
 use tower::layer::Layer;
@@ -32,6 +33,8 @@ pub mod request_spec;
 
 mod route;
 mod tiny_map;
+#[cfg(feature = "websocket")]
+mod websocket;
 
 pub use self::{future::RouterFuture, into_make_service::IntoMakeService, route::Route};
 
@@ -58,6 +61,11 @@ pub use self::{future::RouterFuture, into_make_service::IntoMakeService, route::
 #[derive(Debug)]
 pub struct Router<B = Body> {
     routes: Routes<B>,
+    /// Routes for WebSocket upgrade requests, keyed by exact request path.
+    #[cfg(feature = "websocket")]
+    websocket_routes: Vec<(String, Route<B>)>,
+    /// Route for health checks, keyed by exact request path.
+    health_check: Option<(String, Route<B>)>,
 }
 
 // This constant determines when the `TinyMap` implementation switches from being a `Vec` to a
@@ -80,21 +88,30 @@ enum Routes<B = Body> {
     AwsJson11(TinyMap<String, Route<B>, ROUTE_CUTOFF>),
 }
 
+impl<B> Routes<B> {
+    fn protocol(&self) -> Protocol {
+        match self {
+            Routes::RestJson1(_) => Protocol::RestJson1,
+            Routes::RestXml(_) => Protocol::RestXml,
+            Routes::AwsJson10(_) => Protocol::AwsJson10,
+            Routes::AwsJson11(_) => Protocol::AwsJson11,
+        }
+    }
+}
+
 impl<B> Clone for Router<B> {
     fn clone(&self) -> Self {
-        match &self.routes {
-            Routes::RestJson1(routes) => Router {
-                routes: Routes::RestJson1(routes.clone()),
-            },
-            Routes::RestXml(routes) => Router {
-                routes: Routes::RestXml(routes.clone()),
-            },
-            Routes::AwsJson10(routes) => Router {
-                routes: Routes::AwsJson10(routes.clone()),
-            },
-            Routes::AwsJson11(routes) => Router {
-                routes: Routes::AwsJson11(routes.clone()),
-            },
+        let routes = match &self.routes {
+            Routes::RestJson1(routes) => Routes::RestJson1(routes.clone()),
+            Routes::RestXml(routes) => Routes::RestXml(routes.clone()),
+            Routes::AwsJson10(routes) => Routes::AwsJson10(routes.clone()),
+            Routes::AwsJson11(routes) => Routes::AwsJson11(routes.clone()),
+        };
+        Router {
+            routes,
+            #[cfg(feature = "websocket")]
+            websocket_routes: self.websocket_routes.clone(),
+            health_check: self.health_check.clone(),
         }
     }
 }
@@ -105,14 +122,8 @@ where
 {
     /// Return the correct, protocol-specific "Not Found" response for an unknown operation.
     fn unknown_operation(&self) -> RouterFuture<B> {
-        let protocol = match &self.routes {
-            Routes::RestJson1(_) => Protocol::RestJson1,
-            Routes::RestXml(_) => Protocol::RestXml,
-            Routes::AwsJson10(_) => Protocol::AwsJson10,
-            Routes::AwsJson11(_) => Protocol::AwsJson11,
-        };
         let error = RuntimeError {
-            protocol,
+            protocol: self.routes.protocol(),
             kind: RuntimeErrorKind::UnknownOperation,
         };
         RouterFuture::from_response(error.into_response())
@@ -157,44 +168,177 @@ where
             .layer_fn(Route::new)
             .layer(MapResponseBodyLayer::new(boxed))
             .layer(layer);
-        match self.routes {
-            Routes::RestJson1(routes) => {
-                let routes = routes
+        let routes = match self.routes {
+            Routes::RestJson1(routes) => Routes::RestJson1(
+                routes
                     .into_iter()
                     .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
-                    .collect();
-                Router {
-                    routes: Routes::RestJson1(routes),
-                }
-            }
-            Routes::RestXml(routes) => {
-                let routes = routes
+                    .collect(),
+            ),
+            Routes::RestXml(routes) => Routes::RestXml(
+                routes
                     .into_iter()
                     .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
-                    .collect();
-                Router {
-                    routes: Routes::RestXml(routes),
-                }
-            }
-            Routes::AwsJson10(routes) => {
-                let routes = routes
+                    .collect(),
+            ),
+            Routes::AwsJson10(routes) => Routes::AwsJson10(
+                routes
                     .into_iter()
                     .map(|(operation, route)| (operation, Layer::layer(&layer, route)))
-                    .collect();
-                Router {
-                    routes: Routes::AwsJson10(routes),
-                }
-            }
-            Routes::AwsJson11(routes) => {
-                let routes = routes
+                    .collect(),
+            ),
+            Routes::AwsJson11(routes) => Routes::AwsJson11(
+                routes
                     .into_iter()
                     .map(|(operation, route)| (operation, Layer::layer(&layer, route)))
-                    .collect();
-                Router {
-                    routes: Routes::AwsJson11(routes),
+                    .collect(),
+            ),
+        };
+        Router {
+            routes,
+            #[cfg(feature = "websocket")]
+            websocket_routes: self
+                .websocket_routes
+                .into_iter()
+                .map(|(path, route)| (path, Layer::layer(&layer, route)))
+                .collect(),
+            health_check: self
+                .health_check
+                .map(|(path, route)| (path, Layer::layer(&layer, route))),
+        }
+    }
+
+    /// Add a route that upgrades requests for `path` carrying an `Upgrade: websocket` header to
+    /// a WebSocket connection.
+    ///
+    /// Once the handshake completes, `handler` is spawned with the upgraded connection. The
+    /// connection is a raw byte transport: neither WebSocket nor Smithy framing is applied to
+    /// it, so the handler must bring its own WebSocket implementation. Requests for `path`
+    /// without an `Upgrade: websocket` header are routed to the operations as usual.
+    ///
+    /// Layers applied to the router with [`Router::layer`] afterwards also wrap this route.
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub fn route_websocket<H, F>(mut self, path: impl Into<String>, handler: H) -> Self
+    where
+        B: HttpBody,
+        H: Fn(hyper::upgrade::Upgraded) -> F + Send + Sync + 'static,
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.websocket_routes
+            .push((path.into(), websocket::WebSocketUpgrade::new(handler).into_route()));
+        self
+    }
+
+    /// Add a route for health checks, such as load balancer probes, that responds to any request
+    /// for `path` with the response produced by `handler`.
+    ///
+    /// The request's path is compared to `path` exactly, whatever its method, before any other
+    /// route is considered, so the health check takes precedence over operations and WebSocket
+    /// routes whose paths collide with it: choose a path that no operation uses, such as
+    /// `/healthz`. Calling this again replaces the previous health check.
+    ///
+    /// Layers applied to the router with [`Router::layer`] afterwards also wrap this route.
+    pub fn health_check<H, F, R>(mut self, path: impl Into<String>, handler: H) -> Self
+    where
+        H: Fn() -> F + Send + Sync + 'static,
+        F: std::future::Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        let handler = std::sync::Arc::new(handler);
+        let service = tower::service_fn(move |_req: Request<B>| {
+            let response = handler();
+            async move { Ok::<_, Infallible>(response.await.into_response()) }
+        });
+        self.health_check = Some((path.into(), Route::new(service)));
+        self
+    }
+
+    /// Merge the routes of `other` into this router.
+    ///
+    /// This allows building the routers of groups of operations independently, and serving them
+    /// together. Both routers must use the same protocol, and no request may be routed to both of
+    /// them: an error is returned if they have operations with the same URI pattern and method
+    /// (or, for AwsJson protocols, the same operation name), WebSocket routes with the same path,
+    /// or if they both have a health check.
+    pub fn merge(self, other: Router<B>) -> Result<Self, RouterMergeError> {
+        let routes = match (self.routes, other.routes) {
+            (Routes::RestJson1(routes), Routes::RestJson1(other)) => {
+                Routes::RestJson1(merge_rest_routes(routes, other)?)
+            }
+            (Routes::RestXml(routes), Routes::RestXml(other)) => Routes::RestXml(merge_rest_routes(routes, other)?),
+            (Routes::AwsJson10(routes), Routes::AwsJson10(other)) => {
+                Routes::AwsJson10(merge_operation_routes(routes, other)?)
+            }
+            (Routes::AwsJson11(routes), Routes::AwsJson11(other)) => {
+                Routes::AwsJson11(merge_operation_routes(routes, other)?)
+            }
+            (routes, other) => {
+                return Err(RouterMergeError::ProtocolMismatch {
+                    protocol: routes.protocol(),
+                    other: other.protocol(),
+                })
+            }
+        };
+
+        #[cfg(feature = "websocket")]
+        let websocket_routes = {
+            let mut websocket_routes = self.websocket_routes;
+            for (path, route) in other.websocket_routes {
+                if websocket_routes.iter().any(|(existing, _)| *existing == path) {
+                    return Err(RouterMergeError::ConflictingRoutes(format!("WebSocket route {}", path)));
                 }
+                websocket_routes.push((path, route));
             }
-        }
+            websocket_routes
+        };
+
+        let health_check = match (self.health_check, other.health_check) {
+            (Some(_), Some((path, _))) => {
+                return Err(RouterMergeError::ConflictingRoutes(format!("health check {}", path)))
+            }
+            (health_check, other) => health_check.or(other),
+        };
+
+        Ok(Router {
+            routes,
+            #[cfg(feature = "websocket")]
+            websocket_routes,
+            health_check,
+        })
+    }
+
+    /// Merge the routes of `other` into this router, under the path `prefix`.
+    ///
+    /// The literal segments of `prefix` are prepended to the URI patterns of the operations of
+    /// `other`, and to the paths of its WebSocket routes and health check, before merging them as
+    /// with [`Router::merge`]. For example, an operation bound to `GET /things/{id}` in `other` is
+    /// served at `GET /v1/things/{id}` after nesting it under `/v1`.
+    ///
+    /// Only routers using a REST protocol can be nested: AwsJson operations are all served at `/`.
+    pub fn nest(self, prefix: &str, other: Router<B>) -> Result<Self, RouterMergeError> {
+        let prefix: Vec<String> = prefix
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(String::from)
+            .collect();
+        let prefix_path = |path: String| format!("/{}{}", prefix.join("/"), path);
+        let routes = match other.routes {
+            Routes::RestJson1(routes) => Routes::RestJson1(prefix_rest_routes(routes, &prefix)),
+            Routes::RestXml(routes) => Routes::RestXml(prefix_rest_routes(routes, &prefix)),
+            routes => return Err(RouterMergeError::NestedRpcRouter(routes.protocol())),
+        };
+        let other = Router {
+            routes,
+            #[cfg(feature = "websocket")]
+            websocket_routes: other
+                .websocket_routes
+                .into_iter()
+                .map(|(path, route)| (prefix_path(path), route))
+                .collect(),
+            health_check: other.health_check.map(|(path, route)| (prefix_path(path), route)),
+        };
+        self.merge(other)
     }
 
     /// Create a new RestJson1 `Router` from an iterator over pairs of [`RequestSpec`]s and services.
@@ -222,6 +366,9 @@ where
 
         Self {
             routes: Routes::RestJson1(routes),
+            #[cfg(feature = "websocket")]
+            websocket_routes: Vec::new(),
+            health_check: None,
         }
     }
 
@@ -250,6 +397,9 @@ where
 
         Self {
             routes: Routes::RestXml(routes),
+            #[cfg(feature = "websocket")]
+            websocket_routes: Vec::new(),
+            health_check: None,
         }
     }
 
@@ -273,6 +423,9 @@ where
 
         Self {
             routes: Routes::AwsJson10(routes),
+            #[cfg(feature = "websocket")]
+            websocket_routes: Vec::new(),
+            health_check: None,
         }
     }
 
@@ -296,6 +449,9 @@ where
 
         Self {
             routes: Routes::AwsJson11(routes),
+            #[cfg(feature = "websocket")]
+            websocket_routes: Vec::new(),
+            health_check: None,
         }
     }
 }
@@ -315,6 +471,20 @@ where
 
     #[inline]
     fn call(&mut self, req: Request<B>) -> Self::Future {
+        if let Some((path, route)) = &self.health_check {
+            if path == req.uri().path() {
+                return RouterFuture::from_oneshot(route.clone().oneshot(req));
+            }
+        }
+
+        #[cfg(feature = "websocket")]
+        if websocket::is_upgrade_request(req.headers()) {
+            let route = self.websocket_routes.iter().find(|(path, _)| path == req.uri().path());
+            if let Some((_, route)) = route {
+                return RouterFuture::from_oneshot(route.clone().oneshot(req));
+            }
+        }
+
         match &self.routes {
             // REST routes.
             Routes::RestJson1(routes) | Routes::RestXml(routes) => {
@@ -367,6 +537,82 @@ where
     }
 }
 
+fn merge_rest_routes<B>(
+    mut routes: Vec<(Route<B>, RequestSpec)>,
+    other: Vec<(Route<B>, RequestSpec)>,
+) -> Result<Vec<(Route<B>, RequestSpec)>, RouterMergeError> {
+    for (route, request_spec) in other {
+        if routes
+            .iter()
+            .any(|(_, existing)| existing.conflicts_with(&request_spec))
+        {
+            return Err(RouterMergeError::ConflictingRoutes(request_spec.to_string()));
+        }
+        routes.push((route, request_spec));
+    }
+    // Keep the routes sorted by specificity, like the constructors do
+    routes.sort_by_key(|(_route, request_spec)| std::cmp::Reverse(request_spec.rank()));
+    Ok(routes)
+}
+
+fn merge_operation_routes<B>(
+    routes: TinyMap<String, Route<B>, ROUTE_CUTOFF>,
+    other: TinyMap<String, Route<B>, ROUTE_CUTOFF>,
+) -> Result<TinyMap<String, Route<B>, ROUTE_CUTOFF>, RouterMergeError> {
+    let mut routes: Vec<_> = routes.into_iter().collect();
+    for (operation, route) in other {
+        if routes.iter().any(|(existing, _)| *existing == operation) {
+            return Err(RouterMergeError::ConflictingRoutes(operation));
+        }
+        routes.push((operation, route));
+    }
+    Ok(routes.into_iter().collect())
+}
+
+fn prefix_rest_routes<B>(routes: Vec<(Route<B>, RequestSpec)>, prefix: &[String]) -> Vec<(Route<B>, RequestSpec)> {
+    routes
+        .into_iter()
+        .map(|(route, request_spec)| (route, request_spec.with_path_prefix(prefix)))
+        .collect()
+}
+
+/// Error returned when [`Router::merge`] or [`Router::nest`] can't combine two routers.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum RouterMergeError {
+    /// The routers use different protocols.
+    ProtocolMismatch {
+        /// The protocol of the router being merged into.
+        protocol: Protocol,
+        /// The protocol of the router being merged.
+        other: Protocol,
+    },
+    /// Both routers have a route for the same requests, described by the string.
+    ConflictingRoutes(String),
+    /// The router to nest uses an AwsJson protocol, whose operations can't be nested under a path.
+    NestedRpcRouter(Protocol),
+}
+
+impl fmt::Display for RouterMergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouterMergeError::ProtocolMismatch { protocol, other } => write!(
+                f,
+                "can't merge a router using {:?} into a router using {:?}",
+                other, protocol
+            ),
+            RouterMergeError::ConflictingRoutes(route) => {
+                write!(f, "both routers have a route for `{}`", route)
+            }
+            RouterMergeError::NestedRpcRouter(protocol) => {
+                write!(f, "routers using {:?} can't be nested under a path", protocol)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouterMergeError {}
+
 #[cfg(test)]
 mod rest_tests {
     use super::*;
@@ -592,6 +838,121 @@ mod rest_tests {
             assert_eq!(format!("{} :: {}", svc_name, uri), actual_body);
         }
     }
+
+    #[tokio::test]
+    async fn health_check_takes_precedence() {
+        let request_specs = vec![
+            RequestSpec::from_parts(Method::GET, vec![PathSegment::Label], Vec::new()),
+            RequestSpec::from_parts(
+                Method::GET,
+                vec![PathSegment::Literal(String::from("things")), PathSegment::Label],
+                Vec::new(),
+            ),
+        ];
+        let mut router = Router::new_rest_json_router(request_specs.into_iter().map(|spec| {
+            (
+                tower::util::BoxCloneService::new(NamedEchoUriService(String::from("Operation"))),
+                spec,
+            )
+        }))
+        .health_check("/healthz", || async {
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(boxed(Body::from("healthy")))
+                .unwrap()
+        });
+
+        // `/healthz` would otherwise match the operation with a label.
+        let mut res = router.call(req(&Method::GET, "/healthz", None)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("healthy", get_body_as_string(&mut res).await);
+
+        let mut res = router.call(req(&Method::GET, "/things/a", None)).await.unwrap();
+        assert_eq!("Operation :: /things/a", get_body_as_string(&mut res).await);
+        let mut res = router.call(req(&Method::GET, "/healthy", None)).await.unwrap();
+        assert_eq!("Operation :: /healthy", get_body_as_string(&mut res).await);
+    }
+
+    fn rest_json_router<B>(request_specs: Vec<(RequestSpec, &str)>) -> Router<B>
+    where
+        B: Send + 'static,
+    {
+        Router::new_rest_json_router(request_specs.into_iter().map(|(spec, svc_name)| {
+            (
+                tower::util::BoxCloneService::new(NamedEchoUriService(String::from(svc_name))),
+                spec,
+            )
+        }))
+    }
+
+    fn things_spec(method: Method) -> RequestSpec {
+        RequestSpec::from_parts(
+            method,
+            vec![PathSegment::Literal(String::from("things")), PathSegment::Label],
+            Vec::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn merge_routers() {
+        let router = rest_json_router(vec![(things_spec(Method::GET), "GetThing")]);
+        let other = rest_json_router(vec![
+            (things_spec(Method::DELETE), "DeleteThing"),
+            (
+                RequestSpec::from_parts(
+                    Method::GET,
+                    vec![
+                        PathSegment::Literal(String::from("things")),
+                        PathSegment::Label,
+                        PathSegment::Literal(String::from("parts")),
+                    ],
+                    Vec::new(),
+                ),
+                "ListParts",
+            ),
+        ]);
+        let mut router = router.merge(other).unwrap();
+
+        let mut res = router.call(req(&Method::GET, "/things/a", None)).await.unwrap();
+        assert_eq!("GetThing :: /things/a", get_body_as_string(&mut res).await);
+        let mut res = router.call(req(&Method::DELETE, "/things/a", None)).await.unwrap();
+        assert_eq!("DeleteThing :: /things/a", get_body_as_string(&mut res).await);
+        let mut res = router.call(req(&Method::GET, "/things/a/parts", None)).await.unwrap();
+        assert_eq!("ListParts :: /things/a/parts", get_body_as_string(&mut res).await);
+    }
+
+    #[test]
+    fn merge_conflicting_routers() {
+        let router: Router = rest_json_router(vec![(things_spec(Method::GET), "GetThing")]);
+        let other = rest_json_router(vec![(things_spec(Method::GET), "DescribeThing")]);
+        let err = router.merge(other).unwrap_err();
+        assert!(matches!(err, RouterMergeError::ConflictingRoutes(_)), "{:?}", err);
+        assert_eq!("both routers have a route for `GET /things/{label}`", err.to_string());
+
+        let router: Router = rest_json_router(vec![(things_spec(Method::GET), "GetThing")]);
+        let other = Router::new_aws_json_10_router(std::iter::empty());
+        let err = router.merge(other).unwrap_err();
+        assert!(matches!(err, RouterMergeError::ProtocolMismatch { .. }), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn nest_router() {
+        let router = rest_json_router(vec![(things_spec(Method::GET), "GetThing")]);
+        let other = rest_json_router(vec![(things_spec(Method::GET), "GetThingV1")]).health_check("/ping", || async {
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(boxed(Body::from("healthy")))
+                .unwrap()
+        });
+        let mut router = router.nest("/v1/", other).unwrap();
+
+        let mut res = router.call(req(&Method::GET, "/things/a", None)).await.unwrap();
+        assert_eq!("GetThing :: /things/a", get_body_as_string(&mut res).await);
+        let mut res = router.call(req(&Method::GET, "/v1/things/a", None)).await.unwrap();
+        assert_eq!("GetThingV1 :: /v1/things/a", get_body_as_string(&mut res).await);
+        let mut res = router.call(req(&Method::GET, "/v1/ping", None)).await.unwrap();
+        assert_eq!("healthy", get_body_as_string(&mut res).await);
+    }
 }
 
 #[cfg(test)]
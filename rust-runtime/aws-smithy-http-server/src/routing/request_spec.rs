@@ -5,15 +5,16 @@
 
 use http::Request;
 use regex::Regex;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PathSegment {
     Literal(String),
     Label,
     Greedy,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QuerySegment {
     Key(String),
     KeyValue(String, String),
@@ -221,6 +222,30 @@ impl RequestSpec {
         }
     }
 
+    /// Returns `true` if `other` has the same method and URI pattern, so that no request could be
+    /// routed to one of them rather than the other.
+    pub(super) fn conflicts_with(&self, other: &RequestSpec) -> bool {
+        let query_segments = &self.uri_spec.path_and_query.query_segments.0;
+        let other_query_segments = &other.uri_spec.path_and_query.query_segments.0;
+        self.method == other.method
+            && self.uri_spec.path_and_query.path_segments.0 == other.uri_spec.path_and_query.path_segments.0
+            && query_segments.len() == other_query_segments.len()
+            && query_segments
+                .iter()
+                .all(|segment| other_query_segments.contains(segment))
+    }
+
+    /// Returns a copy of this spec, with the literal segments `prefix` prepended to its path.
+    pub(super) fn with_path_prefix(&self, prefix: &[String]) -> RequestSpec {
+        let mut uri_spec = self.uri_spec.clone();
+        uri_spec
+            .path_and_query
+            .path_segments
+            .0
+            .splice(0..0, prefix.iter().cloned().map(PathSegment::Literal));
+        RequestSpec::new(self.method.clone(), uri_spec)
+    }
+
     // Helper function to build a `RequestSpec`.
     #[cfg(test)]
     pub fn from_parts(
@@ -241,6 +266,32 @@ impl RequestSpec {
     }
 }
 
+/// Formats the spec like the URI pattern of the `@http` trait, e.g. `GET /things/{label}?key`.
+impl fmt::Display for RequestSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.method)?;
+        let path_segments = &self.uri_spec.path_and_query.path_segments.0;
+        if path_segments.is_empty() {
+            f.write_str("/")?;
+        }
+        for segment in path_segments {
+            match segment {
+                PathSegment::Literal(literal) => write!(f, "/{}", literal)?,
+                PathSegment::Label => f.write_str("/{label}")?,
+                PathSegment::Greedy => f.write_str("/{label+}")?,
+            }
+        }
+        for (i, segment) in self.uri_spec.path_and_query.query_segments.0.iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            match segment {
+                QuerySegment::Key(key) => write!(f, "{}{}", separator, key)?,
+                QuerySegment::KeyValue(key, value) => write!(f, "{}{}={}", separator, key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::rest_tests::req;
@@ -0,0 +1,206 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! WebSocket upgrade routes.
+//!
+//! A WebSocket route performs the [RFC 6455] opening handshake and then hands the upgraded
+//! connection to a user-provided handler. The handler receives a [`hyper::upgrade::Upgraded`]
+//! connection, which is a **raw byte transport**: the framework performs no WebSocket framing
+//! and no Smithy (event stream) framing on it. Handlers are expected to layer a WebSocket
+//! implementation on top of it, for example with `tokio_tungstenite::WebSocketStream::from_raw_socket`.
+//!
+//! [RFC 6455]: https://datatracker.ietf.org/doc/html/rfc6455#section-4.2
+
+use super::Route;
+use crate::body::{BoxBody, HttpBody};
+use http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use hyper::upgrade::Upgraded;
+use sha1::{Digest, Sha1};
+use std::{
+    convert::Infallible,
+    fmt,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::Service;
+
+/// The GUID appended to the client's `Sec-WebSocket-Key` to compute `Sec-WebSocket-Accept`.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Returns `true` if the request asks to be upgraded to a WebSocket connection.
+pub(super) fn is_upgrade_request(headers: &HeaderMap) -> bool {
+    header_contains(headers, header::UPGRADE, "websocket")
+}
+
+/// Returns `true` if any of the comma-separated values of the header `name` equals `token`,
+/// ignoring ASCII case.
+fn header_contains(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+fn accept_key(key: &[u8]) -> HeaderValue {
+    let mut sha1 = Sha1::new();
+    sha1.update(key);
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    HeaderValue::try_from(aws_smithy_types::base64::encode(sha1.finalize()))
+        .expect("base64 is always a valid header value")
+}
+
+/// A [`Service`] that completes the WebSocket handshake and spawns the handler on the upgraded
+/// connection.
+#[derive(Clone)]
+pub(super) struct WebSocketUpgrade {
+    handler: Arc<dyn Fn(Upgraded) -> BoxFuture + Send + Sync>,
+}
+
+impl WebSocketUpgrade {
+    pub(super) fn new<H, F>(handler: H) -> Self
+    where
+        H: Fn(Upgraded) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            handler: Arc::new(move |upgraded| Box::pin(handler(upgraded))),
+        }
+    }
+
+    pub(super) fn into_route<B>(self) -> Route<B>
+    where
+        B: HttpBody + Send + 'static,
+    {
+        Route::new(self)
+    }
+}
+
+impl fmt::Debug for WebSocketUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketUpgrade").finish()
+    }
+}
+
+impl<B> Service<Request<B>> for WebSocketUpgrade
+where
+    B: HttpBody + Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let headers = req.headers();
+        let key = match headers.get(header::SEC_WEBSOCKET_KEY) {
+            Some(key)
+                if req.method() == Method::GET
+                    && header_contains(headers, header::CONNECTION, "upgrade")
+                    && headers.get(header::SEC_WEBSOCKET_VERSION).map(|v| v.as_bytes()) == Some(b"13") =>
+            {
+                key
+            }
+            _ => {
+                let mut response = Response::new(crate::body::empty());
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                return ready(Ok(response));
+            }
+        };
+        let accept = accept_key(key.as_bytes());
+
+        let on_upgrade = hyper::upgrade::on(&mut req);
+        let handler = self.handler.clone();
+        tokio::spawn(async move {
+            // The upgrade fails if the client disconnects before the handshake completes, in
+            // which case there is nothing left to hand to the handler.
+            if let Ok(upgraded) = on_upgrade.await {
+                handler(upgraded).await;
+            }
+        });
+
+        let response = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+            .body(crate::body::empty())
+            .expect("valid WebSocket handshake response");
+        ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body::Body, Router};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{protocol::Role, Message};
+    use tokio_tungstenite::WebSocketStream;
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        // https://datatracker.ietf.org/doc/html/rfc6455#section-1.3
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="));
+    }
+
+    #[test]
+    fn upgrade_detection_is_case_insensitive() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_upgrade_request(&headers));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("h2c, WebSocket"));
+        assert!(is_upgrade_request(&headers));
+    }
+
+    #[tokio::test]
+    async fn websocket_route_echoes_text_frames() {
+        let router =
+            Router::<Body>::new_rest_json_router(std::iter::empty()).route_websocket("/echo", |upgraded| async {
+                let mut ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                while let Some(Ok(message)) = ws.next().await {
+                    if message.is_text() {
+                        ws.send(message).await.unwrap();
+                    }
+                }
+            });
+
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let (mut ws, response) = tokio_tungstenite::connect_async(format!("ws://{}/echo", addr))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+        ws.send(Message::Text("hello".into())).await.unwrap();
+        assert_eq!(Message::Text("hello".into()), ws.next().await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn invalid_handshake_is_rejected() {
+        let mut router =
+            Router::<Body>::new_rest_json_router(std::iter::empty()).route_websocket("/echo", |_| async {});
+        let req = Request::builder()
+            .uri("/echo")
+            .header(header::UPGRADE, "websocket")
+            .body(Body::empty())
+            .unwrap();
+        let res = router.call(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // Requests that don't ask to be upgraded use the regular routes.
+        let req = Request::builder().uri("/echo").body(Body::empty()).unwrap();
+        let res = router.call(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+}
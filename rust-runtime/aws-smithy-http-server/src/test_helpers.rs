@@ -3,5 +3,44 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use crate::body::{Body, HttpBody};
+use crate::error::BoxError;
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use std::convert::Infallible;
+use tower::{service_fn, Service};
+
 pub(crate) fn assert_send<T: Send>() {}
 pub(crate) fn assert_sync<T: Sync>() {}
+
+/// Reads the whole request body, then responds with `response_body(request_body)`, or with
+/// `400 Bad Request` and the error message if the request body could not be read.
+pub(crate) fn read_body_service<B, F>(
+    response_body: F,
+) -> impl Service<Request<B>, Response = Response<Body>, Error = Infallible, Future = impl Send> + Clone
+where
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+    F: Fn(Bytes) -> Body + Clone + Send + 'static,
+{
+    service_fn(move |req: Request<B>| {
+        let response_body = response_body.clone();
+        async move {
+            let response = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => Response::new(response_body(body)),
+                Err(err) => {
+                    let mut response = Response::new(Body::from(err.into().to_string()));
+                    *response.status_mut() = StatusCode::BAD_REQUEST;
+                    response
+                }
+            };
+            Ok(response)
+        }
+    })
+}
+
+/// A request without a `Content-Length`, whose body is `chunks` chunks of `chunk_len` bytes.
+pub(crate) fn chunked_request(chunks: usize, chunk_len: usize) -> Request<Body> {
+    let stream = futures_util::stream::iter((0..chunks).map(move |_| Ok::<_, std::io::Error>(vec![b'x'; chunk_len])));
+    Request::new(Body::wrap_stream(stream))
+}
@@ -0,0 +1,215 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Serving a [`Router`] over a Unix domain socket.
+//!
+//! Local clients, such as a sidecar proxy, can reach a server listening on a Unix domain socket
+//! without going through the TCP stack. [`bind_hyper_uds`] binds the socket and returns the
+//! [`hyper::Server`] serving the router on it, which can be run as is or with
+//! [`with_graceful_shutdown`](hyper::Server::with_graceful_shutdown):
+//!
+//! ```rust,ignore
+//! let server = bind_hyper_uds("/run/service.sock", router)?;
+//! server.with_graceful_shutdown(shutdown_signal()).await?;
+//! ```
+//!
+//! The socket file isn't removed when the server shuts down. Instead, binding to a path left over
+//! by a previous server removes it, as long as it's a socket nothing is listening on anymore.
+
+use crate::routing::{IntoMakeService, Router};
+use hyper::server::accept::Accept;
+use std::fs::{DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::{UnixListener, UnixStream};
+
+/// A [`hyper::Server`] serving a [`Router`] over a Unix domain socket.
+pub type UdsServer = hyper::Server<UnixAccept, IntoMakeService<Router>>;
+
+/// Bind a Unix domain socket at `path`, and serve `router` over it.
+///
+/// The socket is created with the default permissions, which depend on the umask of the process:
+/// use [`bind_hyper_uds_with_permissions`] to restrict which users can connect to it.
+///
+/// This must be called from within a Tokio runtime.
+pub fn bind_hyper_uds(path: impl AsRef<Path>, router: Router) -> io::Result<UdsServer> {
+    let listener = bind(path.as_ref())?;
+    Ok(serve(listener, router))
+}
+
+/// Bind a Unix domain socket at `path` with the permissions `mode` (e.g. `0o660`), and serve
+/// `router` over it.
+///
+/// The socket is bound in a directory next to `path` that only the current user can access, and
+/// moved to `path` once its permissions are set, so that no other user can connect to it before
+/// then. The directory containing `path` must therefore be writable.
+///
+/// This must be called from within a Tokio runtime.
+pub fn bind_hyper_uds_with_permissions(path: impl AsRef<Path>, router: Router, mode: u32) -> io::Result<UdsServer> {
+    let path = path.as_ref();
+    remove_stale_socket(path)?;
+    let staging_dir = create_staging_dir(path)?;
+    let staged = staging_dir.join("socket");
+    let listener = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    // Nothing is left in the directory once the socket was moved
+    let _ = std::fs::remove_dir_all(&staging_dir);
+    Ok(serve(listener?, router))
+}
+
+/// Creates a directory next to `path` that only the current user can access.
+fn create_staging_dir(path: &Path) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let staging_dir = parent.join(format!(".{}.{:016x}", file_name, fastrand::u64(..)));
+    DirBuilder::new().mode(0o700).create(&staging_dir)?;
+    Ok(staging_dir)
+}
+
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    remove_stale_socket(path)?;
+    UnixListener::bind(path)
+}
+
+fn serve(listener: UnixListener, router: Router) -> UdsServer {
+    hyper::Server::builder(UnixAccept { listener }).serve(router.into_make_service())
+}
+
+/// Removes the socket file at `path` if no server is listening on it anymore.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("`{}` already exists and isn't a socket", path.display()),
+        ));
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("a server is already listening on `{}`", path.display()),
+        )),
+        Err(_) => std::fs::remove_file(path),
+    }
+}
+
+/// Accepts the connections made to a [`UdsServer`].
+#[derive(Debug)]
+pub struct UnixAccept {
+    listener: UnixListener,
+}
+
+impl Accept for UnixAccept {
+    type Conn = UnixStream;
+    type Error = io::Error;
+
+    fn poll_accept(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|accepted| Some(accepted.map(|(stream, _)| stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{boxed, Body, BoxBody};
+    use http::{Request, Response};
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tower::service_fn;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("aws-smithy-http-server-{}-{}.sock", std::process::id(), name))
+    }
+
+    /// A router with a single AwsJson 1.0 operation that responds with `hello`.
+    fn router() -> Router {
+        let operation = service_fn(|_req: Request<Body>| async {
+            Ok::<_, Infallible>(Response::<BoxBody>::new(boxed(Body::from("hello"))))
+        });
+        Router::new_aws_json_10_router([(
+            tower::util::BoxCloneService::new(operation),
+            "Service.Operation".to_string(),
+        )])
+    }
+
+    async fn send_request(path: &Path) -> String {
+        let mut stream = UnixStream::connect(path).await.unwrap();
+        stream
+            .write_all(
+                b"POST / HTTP/1.1\r\n\
+                host: localhost\r\n\
+                x-amz-target: Service.Operation\r\n\
+                content-length: 0\r\n\
+                connection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn routed_request_over_unix_socket() {
+        let path = socket_path("routed");
+        let server = bind_hyper_uds_with_permissions(&path, router(), 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        // The directory the socket was bound in was removed
+        let staging_prefix = format!(".{}.", path.file_name().unwrap().to_string_lossy());
+        let leftovers = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&staging_prefix)
+            })
+            .count();
+        assert_eq!(0, leftovers);
+
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = shutdown_signal.await;
+        }));
+
+        let response = send_request(&path).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("hello"), "{}", response);
+
+        shutdown.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_socket_is_replaced() {
+        let path = socket_path("stale");
+        let stale = bind_hyper_uds(&path, router()).unwrap();
+        assert_eq!(
+            io::ErrorKind::AddrInUse,
+            bind_hyper_uds(&path, router()).unwrap_err().kind()
+        );
+        drop(stale);
+
+        let server = tokio::spawn(bind_hyper_uds(&path, router()).unwrap());
+        assert!(send_request(&path).await.starts_with("HTTP/1.1 200 OK"));
+        server.abort();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
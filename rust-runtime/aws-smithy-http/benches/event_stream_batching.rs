@@ -0,0 +1,85 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_smithy_eventstream::error::Error as EventStreamError;
+use aws_smithy_eventstream::frame::{MarshallMessage, Message, SignMessage, SignMessageError};
+use aws_smithy_http::event_stream::MessageStreamAdapter;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures_util::{FutureExt, StreamExt};
+use std::error::Error as StdError;
+use std::fmt;
+
+const EVENT_COUNT: usize = 10_000;
+
+#[derive(Debug)]
+struct Marshaller;
+impl MarshallMessage for Marshaller {
+    type Input = &'static [u8];
+
+    fn marshall(&self, input: Self::Input) -> Result<Message, EventStreamError> {
+        Ok(Message::new(input))
+    }
+}
+
+#[derive(Debug)]
+struct NoOpSigner;
+impl SignMessage for NoOpSigner {
+    fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+        Ok(message)
+    }
+
+    fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+        None
+    }
+}
+
+#[derive(Debug)]
+struct BenchError;
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BenchError")
+    }
+}
+impl StdError for BenchError {}
+
+/// Drains an adapter over a source that always has an event ready, returning the number of chunks
+/// it yielded.
+fn drain(max_batch_bytes: usize) -> usize {
+    let source = futures_util::stream::iter((0..EVENT_COUNT).map(|_| Ok(&b"small event"[..])));
+    let mut adapter =
+        MessageStreamAdapter::<_, BenchError>::new(Marshaller, NoOpSigner, Box::pin(source))
+            .with_max_batch_bytes(max_batch_bytes);
+    let mut chunks = 0;
+    while let Some(chunk) = adapter
+        .next()
+        .now_or_never()
+        .expect("the source is always ready")
+    {
+        chunk.expect("valid event");
+        chunks += 1;
+    }
+    chunks
+}
+
+fn batching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("event_stream_batching");
+    for max_batch_bytes in [0, 16 * 1024, 64 * 1024] {
+        println!(
+            "max_batch_bytes = {}: {} events yielded as {} chunks",
+            max_batch_bytes,
+            EVENT_COUNT,
+            drain(max_batch_bytes)
+        );
+        group.bench_with_input(
+            BenchmarkId::from_parameter(max_batch_bytes),
+            &max_batch_bytes,
+            |b, &max_batch_bytes| b.iter(|| drain(max_batch_bytes)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, batching);
+criterion_main!(benches);
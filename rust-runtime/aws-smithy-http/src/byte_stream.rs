@@ -375,6 +375,29 @@ impl ByteStream {
     /// Set a callback on this `ByteStream`. The callback's methods will be called at various points
     /// throughout this `ByteStream`'s life cycle. See the [`BodyCallback`](BodyCallback) trait for
     /// more information.
+    ///
+    /// Callbacks can be set on a `ByteStream` however it was created: from in-memory data, from a
+    /// file, or from an [`SdkBody`] wrapping any other source, such as a reader. They don't read
+    /// the stream eagerly: each chunk is passed to [`update`](BodyCallback::update) as the stream
+    /// is polled, so the callback only sees all of the data once the stream has been drained. The
+    /// [`trailers`](BodyCallback::trailers) of the callbacks are those of the underlying
+    /// [`SdkBody`], which can be read after draining it:
+    ///
+    /// ```no_run
+    /// # use aws_smithy_http::callback::BodyCallback;
+    /// use aws_smithy_http::byte_stream::ByteStream;
+    /// use http_body::Body;
+    ///
+    /// async fn trailers(checksum_callback: Box<dyn BodyCallback>) {
+    ///     let mut stream = ByteStream::from_static(b"hello world");
+    ///     stream.with_body_callback(checksum_callback);
+    ///     let mut body = stream.into_inner();
+    ///     while let Some(data) = body.data().await {
+    ///         let _ = data.expect("no errors");
+    ///     }
+    ///     let trailers = body.trailers().await.expect("no errors");
+    /// }
+    /// ```
     pub fn with_body_callback(&mut self, body_callback: Box<dyn BodyCallback>) -> &mut Self {
         self.inner.with_body_callback(body_callback);
         self
@@ -592,8 +615,41 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::byte_stream::Inner;
+    use crate::byte_stream::{ByteStream, Inner};
+    use crate::callback::BodyCallback;
     use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue};
+    use http_body::Body;
+
+    /// Counts the bytes of a body, and sends the count as an `x-byte-count` trailer
+    #[derive(Default)]
+    struct ByteCountCallback(usize);
+
+    impl BodyCallback for ByteCountCallback {
+        fn update(&mut self, bytes: &[u8]) -> Result<(), crate::body::Error> {
+            self.0 += bytes.len();
+            Ok(())
+        }
+
+        fn trailers(&self) -> Result<Option<HeaderMap<HeaderValue>>, crate::body::Error> {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-byte-count", HeaderValue::from(self.0));
+            Ok(Some(trailers))
+        }
+
+        fn make_new(&self) -> Box<dyn BodyCallback> {
+            Box::new(ByteCountCallback::default())
+        }
+    }
+
+    /// Drains `stream`, returning the trailers of its callbacks
+    async fn drain(stream: ByteStream) -> Option<HeaderMap<HeaderValue>> {
+        let mut body = stream.into_inner();
+        while let Some(data) = body.data().await {
+            data.expect("no errors");
+        }
+        body.trailers().await.expect("no errors")
+    }
 
     #[tokio::test]
     async fn read_from_string_body() {
@@ -665,6 +721,26 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn body_callback_on_static_bytestream() {
+        let mut stream = ByteStream::from_static(b"hello world");
+        stream.with_body_callback(Box::new(ByteCountCallback::default()));
+
+        let trailers = drain(stream).await.expect("the callback sets trailers");
+        assert_eq!("11", trailers["x-byte-count"]);
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn body_callback_on_reader_bytestream() {
+        let reader = tokio_util::io::ReaderStream::with_capacity(&b"data 1\ndata 2\n"[..], 4);
+        let mut stream = ByteStream::new(hyper::Body::wrap_stream(reader).into());
+        stream.with_body_callback(Box::new(ByteCountCallback::default()));
+
+        let trailers = drain(stream).await.expect("the callback sets trailers");
+        assert_eq!("14", trailers["x-byte-count"]);
+    }
+
     #[cfg(feature = "rt-tokio")]
     #[tokio::test]
     async fn bytestream_into_async_read() {
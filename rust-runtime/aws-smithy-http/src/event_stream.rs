@@ -7,13 +7,31 @@
 
 use std::error::Error as StdError;
 
+#[cfg(feature = "event-stream-compression")]
+mod compression;
 mod input;
+mod metrics;
 mod output;
 
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
 pub type BoxError = Box<dyn StdError + Send + Sync + 'static>;
 
 #[doc(inline)]
-pub use input::{EventStreamInput, MessageStreamAdapter};
+pub use input::{
+    BroadcastLagPolicy, BroadcastLaggedError, CloseHandle, ConstructionPhase, EndHandle,
+    EventStreamConstructionError, EventStreamInput, EventStreamInputHandle, FrameInspector,
+    FrameTooLargeError, MessageDecorator, MessageStreamAdapter, SendError, TimestampDecorator,
+    TracingFrameInspector,
+};
+
+#[cfg(feature = "event-stream-compression")]
+#[doc(inline)]
+pub use compression::PayloadCompression;
+
+#[doc(inline)]
+pub use metrics::EventStreamMetrics;
 
 #[doc(inline)]
-pub use output::{Error, RawMessage, Receiver};
+pub use output::{Error, RawMessage, Receiver, UnknownEvent, UnknownEventPolicy};
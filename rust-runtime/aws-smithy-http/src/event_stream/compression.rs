@@ -0,0 +1,222 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Gzip compression of event payloads, signaled with the `:content-encoding` message header.
+
+use aws_smithy_eventstream::frame::{Header, HeaderValue, Message};
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{self, Read, Write};
+
+/// Message header naming the encoding of the payload
+const CONTENT_ENCODING: &str = ":content-encoding";
+const GZIP: &str = "gzip";
+
+/// Options for compressing the payloads of events sent by a
+/// [`MessageStreamAdapter`](super::MessageStreamAdapter), see
+/// [`with_payload_compression`](super::MessageStreamAdapter::with_payload_compression).
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct PayloadCompression {
+    min_payload_size_bytes: usize,
+}
+
+impl PayloadCompression {
+    /// Create a new [`PayloadCompression`] that compresses payloads of any size
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Payloads smaller than `min_payload_size_bytes` are sent uncompressed.
+    pub fn with_min_payload_size_bytes(mut self, min_payload_size_bytes: usize) -> Self {
+        self.min_payload_size_bytes = min_payload_size_bytes;
+        self
+    }
+
+    /// Gzips the payload of `message` and sets its `:content-encoding` header, unless the payload
+    /// is too small to be worth compressing.
+    pub(crate) fn compress(&self, message: Message) -> io::Result<Message> {
+        if message.payload().len() < self.min_payload_size_bytes {
+            return Ok(message);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(message.payload())?;
+        let compressed = encoder.finish()?;
+        Ok(
+            Message::new_from_parts(message.headers().to_vec(), compressed).add_header(
+                Header::new(CONTENT_ENCODING, HeaderValue::String(GZIP.into())),
+            ),
+        )
+    }
+}
+
+/// Decompresses the payload of `message` if its `:content-encoding` header is `gzip`.
+///
+/// Returns `Ok(None)` if the payload isn't compressed. The header is removed from the returned
+/// message, so unmarshallers see the message as it was before it was compressed.
+pub(crate) fn decompress(message: &Message) -> io::Result<Option<Message>> {
+    let gzipped = message.headers().iter().any(|header| {
+        header.name().as_str() == CONTENT_ENCODING
+            && matches!(header.value().as_string(), Ok(encoding) if encoding.as_str() == GZIP)
+    });
+    if !gzipped {
+        return Ok(None);
+    }
+    let mut payload = Vec::new();
+    GzDecoder::new(&message.payload()[..]).read_to_end(&mut payload)?;
+    let headers = message
+        .headers()
+        .iter()
+        .filter(|header| header.name().as_str() != CONTENT_ENCODING)
+        .cloned()
+        .collect();
+    Ok(Some(Message::new_from_parts(headers, Bytes::from(payload))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PayloadCompression;
+    use crate::body::SdkBody;
+    use crate::event_stream::{MessageStreamAdapter, Receiver};
+    use crate::result::SdkError;
+    use async_stream::stream;
+    use aws_smithy_eventstream::error::Error as EventStreamError;
+    use aws_smithy_eventstream::frame::{
+        Header, HeaderValue, MarshallMessage, Message, SignMessage, SignMessageError,
+        UnmarshallMessage, UnmarshalledMessage,
+    };
+    use bytes::BytesMut;
+    use futures_util::StreamExt;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct TestError;
+    impl std::fmt::Display for TestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "TestError")
+        }
+    }
+    impl std::error::Error for TestError {}
+
+    /// Marshalls strings into `text` events
+    #[derive(Debug)]
+    struct TextMarshaller;
+    impl MarshallMessage for TextMarshaller {
+        type Input = String;
+
+        fn marshall(&self, input: Self::Input) -> Result<Message, EventStreamError> {
+            Ok(Message::new(input.into_bytes()).add_header(Header::new(
+                ":event-type",
+                HeaderValue::String("text".into()),
+            )))
+        }
+    }
+
+    /// Sends messages as they are, so that receivers can read them directly
+    #[derive(Debug)]
+    struct NoOpSigner;
+    impl SignMessage for NoOpSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            Ok(message)
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            None
+        }
+    }
+
+    /// Unmarshalls `text` events into strings, failing on any other header
+    #[derive(Debug)]
+    struct TextUnmarshaller;
+    impl UnmarshallMessage for TextUnmarshaller {
+        type Output = String;
+        type Error = TestError;
+
+        fn unmarshall(
+            &self,
+            message: &Message,
+        ) -> Result<UnmarshalledMessage<Self::Output, Self::Error>, EventStreamError> {
+            assert_eq!(1, message.headers().len(), "{:?}", message.headers());
+            Ok(UnmarshalledMessage::Event(
+                String::from_utf8(message.payload().to_vec()).unwrap(),
+            ))
+        }
+    }
+
+    async fn send(events: Vec<String>, compression: PayloadCompression) -> (SdkBody, Vec<Message>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let inspected = sent.clone();
+        let mut adapter = MessageStreamAdapter::<String, TestError>::new(
+            TextMarshaller,
+            NoOpSigner,
+            Box::pin(stream! {
+                for event in events {
+                    yield Ok(event);
+                }
+            }),
+        )
+        .with_end_frame(false)
+        .with_payload_compression(compression)
+        .with_frame_inspector(move |message: &Message, _: &bytes::Bytes| {
+            inspected.lock().unwrap().push(message.clone())
+        });
+        let mut body = BytesMut::new();
+        while let Some(frame) = adapter.next().await {
+            body.extend_from_slice(&frame.unwrap());
+        }
+        let sent = sent.lock().unwrap().clone();
+        (SdkBody::from(body.freeze()), sent)
+    }
+
+    async fn receive(body: SdkBody) -> Vec<String> {
+        let mut receiver = Receiver::new(TextUnmarshaller, body);
+        let mut events = Vec::new();
+        while let Some(event) = receiver.recv().await.unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn payload_compression_round_trip() {
+        let large = "event stream ".repeat(100);
+        let events = vec![large.clone(), "small".to_string()];
+        let compression = PayloadCompression::new().with_min_payload_size_bytes(64);
+        let (body, sent) = send(events.clone(), compression).await;
+
+        // Only the large payload was compressed
+        assert!(sent[0].payload().len() < large.len());
+        assert!(sent[0]
+            .headers()
+            .iter()
+            .any(|header| header.name().as_str() == ":content-encoding"));
+        assert_eq!(b"small", &sent[1].payload()[..]);
+        assert_eq!(1, sent[1].headers().len());
+
+        assert_eq!(events, receive(body).await);
+    }
+
+    #[tokio::test]
+    async fn corrupt_compressed_payload() {
+        let message = Message::new(&b"not gzip"[..])
+            .add_header(Header::new(
+                ":event-type",
+                HeaderValue::String("text".into()),
+            ))
+            .add_header(Header::new(
+                ":content-encoding",
+                HeaderValue::String("gzip".into()),
+            ));
+        let mut body = Vec::new();
+        message.write_to(&mut body).unwrap();
+        let mut receiver = Receiver::new(TextUnmarshaller, SdkBody::from(body));
+        assert!(matches!(
+            receiver.recv().await,
+            Err(SdkError::ResponseError { .. })
+        ));
+    }
+}
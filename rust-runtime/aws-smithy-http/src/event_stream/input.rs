@@ -3,20 +3,37 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use super::BoxError;
+use super::{BoxError, EventStreamMetrics};
 use crate::result::SdkError;
-use aws_smithy_eventstream::frame::{MarshallMessage, SignMessage};
-use bytes::Bytes;
-use futures_core::Stream;
+use aws_smithy_eventstream::error::Error as EventStreamError;
+use aws_smithy_eventstream::frame::{
+    write_headers_to, Header, HeaderValue, MarshallMessage, Message, SignMessage,
+};
+use aws_smithy_types::DateTime;
+use bytes::{Bytes, BytesMut};
+use futures_core::{ready, Stream};
 use std::error::Error as StdError;
 use std::fmt;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{Instant, Sleep};
+use tracing::field::Empty;
+use tracing::{debug, trace, trace_span};
+
+/// The stream of events sent over an event stream
+type InputStream<T> = Pin<Box<dyn Stream<Item = Result<T, BoxError>> + Send>>;
 
 /// Input type for Event Streams.
 pub struct EventStreamInput<T> {
-    input_stream: Pin<Box<dyn Stream<Item = Result<T, BoxError>> + Send>>,
+    input_stream: InputStream<T>,
+    end_handle: EndHandle,
+    buffer: Arc<BufferState>,
 }
 
 impl<T> fmt::Debug for EventStreamInput<T> {
@@ -26,13 +43,349 @@ impl<T> fmt::Debug for EventStreamInput<T> {
 }
 
 impl<T> EventStreamInput<T> {
+    /// Returns a handle that [ends](EndHandle::end) this event stream without closing the request
+    /// body it is sent over.
+    pub fn end_handle(&self) -> EndHandle {
+        self.end_handle.clone()
+    }
+
+    /// Returns a handle that [closes](CloseHandle::close) or [aborts](CloseHandle::abort) this
+    /// event stream without waiting for the input stream to end.
+    pub fn close_handle(&self) -> CloseHandle {
+        self.end_handle.close_handle()
+    }
+
     #[doc(hidden)]
     pub fn into_body_stream<E: StdError + Send + Sync + 'static>(
         self,
         marshaller: impl MarshallMessage<Input = T> + Send + Sync + 'static,
         signer: impl SignMessage + Send + Sync + 'static,
     ) -> MessageStreamAdapter<T, E> {
-        MessageStreamAdapter::new(marshaller, signer, self.input_stream)
+        let mut adapter = MessageStreamAdapter::new(marshaller, signer, self.input_stream);
+        adapter.end_handle = self.end_handle;
+        adapter.buffer = self.buffer;
+        adapter
+    }
+}
+
+impl<T: Send + 'static> EventStreamInput<T> {
+    /// Sends `message` ahead of the events of the input stream.
+    ///
+    /// Some operations expect a leading message carrying operation-level metadata. It is
+    /// marshalled and signed like any other event, and since it is signed first, its signature
+    /// seeds the signature chain of the events that follow.
+    pub fn with_initial_message(mut self, message: T) -> Self {
+        self.input_stream = Box::pin(Prepended {
+            first: Some(message),
+            rest: self.input_stream,
+        });
+        self
+    }
+
+    /// Creates an event stream fed by a channel holding up to `buffer` events.
+    ///
+    /// Events sent with the returned [`EventStreamInputHandle`] are sent in order. The event
+    /// stream ends once the handle is [closed](EventStreamInputHandle::close), or once it and all
+    /// of its clones are dropped.
+    ///
+    /// Once the channel is full, [`send`](EventStreamInputHandle::send) waits until the event
+    /// stream takes an event from it. Combined with the buffer limits of the
+    /// [`MessageStreamAdapter`] (see
+    /// [`with_max_buffered_bytes`](MessageStreamAdapter::with_max_buffered_bytes)), this caps the
+    /// memory held between the producer and the network.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is 0.
+    pub fn channel(buffer: usize) -> (EventStreamInputHandle<T>, Self) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let input = Self::from(ChannelStream { receiver });
+        let handle = EventStreamInputHandle {
+            sender: Arc::new(Mutex::new(Some(sender))),
+            buffer: input.buffer.clone(),
+        };
+        (handle, input)
+    }
+
+    /// Creates an event stream sending the events received from a broadcast channel.
+    ///
+    /// The event stream ends once all senders of the channel are dropped. If the event stream
+    /// falls so far behind that the channel drops events before they were received, `lag_policy`
+    /// decides what is sent in their place.
+    pub fn from_broadcast(
+        receiver: broadcast::Receiver<T>,
+        lag_policy: BroadcastLagPolicy<T>,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        Self::from(BroadcastStream {
+            recv: Some(Box::pin(broadcast_recv(receiver))),
+            lag_policy,
+        })
+    }
+
+    /// Creates an event stream sending the value of a watch channel every time it changes.
+    ///
+    /// The value at the time the event stream is created isn't sent, unless it wasn't seen yet by
+    /// `receiver`. Changes that happen while the event stream is busy are coalesced: only the
+    /// latest value is sent. The event stream ends once the sender is dropped.
+    pub fn from_watch(receiver: watch::Receiver<T>) -> Self
+    where
+        T: Clone + Sync,
+    {
+        Self::from(WatchStream {
+            changed: Some(Box::pin(watch_changed(receiver))),
+        })
+    }
+}
+
+/// Sends events to an [`EventStreamInput`] created with [`EventStreamInput::channel`].
+#[derive(Debug)]
+pub struct EventStreamInputHandle<T> {
+    // `None` once closed. Shared so that closing any clone closes the channel.
+    sender: Arc<Mutex<Option<mpsc::Sender<T>>>>,
+    buffer: Arc<BufferState>,
+}
+
+impl<T> Clone for EventStreamInputHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl<T> EventStreamInputHandle<T> {
+    /// Sends an event, waiting for space in the channel if it is full.
+    ///
+    /// Fails, returning the event, if the event stream was closed or dropped.
+    pub async fn send(&self, event: T) -> Result<(), SendError<T>> {
+        let sender = self.sender.lock().unwrap().clone();
+        match sender {
+            Some(sender) => sender
+                .send(event)
+                .await
+                .map_err(|err| SendError { event: err.0 }),
+            None => Err(SendError { event }),
+        }
+    }
+
+    /// Closes the channel. The event stream ends after the events already sent.
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+
+    /// Returns the number of bytes of frames that the event stream handed to the HTTP client, but
+    /// that haven't been sent yet. Events still in the channel aren't included.
+    ///
+    /// Frames are only counted while a buffer limit is set on the adapter, with
+    /// [`with_max_buffered_frames`](MessageStreamAdapter::with_max_buffered_frames) or
+    /// [`with_max_buffered_bytes`](MessageStreamAdapter::with_max_buffered_bytes).
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.bytes()
+    }
+}
+
+/// Error returned by [`EventStreamInputHandle::send`] when the event stream is closed.
+pub struct SendError<T> {
+    event: T,
+}
+
+impl<T> SendError<T> {
+    /// Returns the event that couldn't be sent.
+    pub fn into_inner(self) -> T {
+        self.event
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the event stream was closed")
+    }
+}
+
+impl<T> StdError for SendError<T> {}
+
+/// Adapts the receiving end of a channel to the event stream's input stream.
+struct ChannelStream<T> {
+    receiver: mpsc::Receiver<T>,
+}
+
+impl<T> Stream for ChannelStream<T> {
+    type Item = Result<T, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx).map(|event| event.map(Ok))
+    }
+}
+
+/// Yields `first` before the items of the `rest` stream.
+struct Prepended<T> {
+    first: Option<T>,
+    rest: InputStream<T>,
+}
+
+// `first` is never pinned
+impl<T> Unpin for Prepended<T> {}
+
+impl<T> Stream for Prepended<T> {
+    type Item = Result<T, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.first.take() {
+            Some(first) => Poll::Ready(Some(Ok(first))),
+            None => self.rest.as_mut().poll_next(cx),
+        }
+    }
+}
+
+/// What an event stream created with [`EventStreamInput::from_broadcast`] sends when it lagged
+/// behind its broadcast channel, and the channel dropped events before they were received.
+#[non_exhaustive]
+pub enum BroadcastLagPolicy<T> {
+    /// Fail the event stream with a [`BroadcastLaggedError`].
+    Error,
+    /// Send the event created from the number of dropped events, e.g. an event telling the
+    /// receiver that it missed some events, and carry on.
+    Synthesize(fn(u64) -> T),
+}
+
+impl<T> Clone for BroadcastLagPolicy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BroadcastLagPolicy<T> {}
+
+impl<T> fmt::Debug for BroadcastLagPolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Synthesize(_) => write!(f, "Synthesize(..)"),
+        }
+    }
+}
+
+/// Error sent by an event stream created with [`EventStreamInput::from_broadcast`] and
+/// [`BroadcastLagPolicy::Error`] when it lagged behind its broadcast channel.
+#[derive(Debug)]
+pub struct BroadcastLaggedError {
+    skipped: u64,
+}
+
+impl BroadcastLaggedError {
+    /// Returns the number of events that were dropped by the channel before they were received.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl fmt::Display for BroadcastLaggedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the event stream lagged behind its broadcast channel, which dropped {} events",
+            self.skipped
+        )
+    }
+}
+
+impl StdError for BroadcastLaggedError {}
+
+type BroadcastRecv<T> = Pin<
+    Box<
+        dyn Future<
+                Output = (
+                    Result<T, broadcast::error::RecvError>,
+                    broadcast::Receiver<T>,
+                ),
+            > + Send,
+    >,
+>;
+
+// Receivers are moved in and out of the future, since `recv` borrows them
+async fn broadcast_recv<T: Clone>(
+    mut receiver: broadcast::Receiver<T>,
+) -> (
+    Result<T, broadcast::error::RecvError>,
+    broadcast::Receiver<T>,
+) {
+    let result = receiver.recv().await;
+    (result, receiver)
+}
+
+/// Adapts a broadcast channel receiver to the event stream's input stream.
+struct BroadcastStream<T> {
+    // `None` once the channel was closed
+    recv: Option<BroadcastRecv<T>>,
+    lag_policy: BroadcastLagPolicy<T>,
+}
+
+impl<T: Clone + Send + 'static> Stream for BroadcastStream<T> {
+    type Item = Result<T, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let recv = match &mut self.recv {
+            Some(recv) => recv,
+            None => return Poll::Ready(None),
+        };
+        let (result, receiver) = ready!(recv.as_mut().poll(cx));
+        self.recv = Some(Box::pin(broadcast_recv(receiver)));
+        Poll::Ready(match result {
+            Ok(event) => Some(Ok(event)),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => match self.lag_policy {
+                BroadcastLagPolicy::Error => Some(Err(Box::new(BroadcastLaggedError { skipped }))),
+                BroadcastLagPolicy::Synthesize(synthesize) => Some(Ok(synthesize(skipped))),
+            },
+            Err(broadcast::error::RecvError::Closed) => {
+                self.recv = None;
+                None
+            }
+        })
+    }
+}
+
+type WatchChanged<T> =
+    Pin<Box<dyn Future<Output = (Result<(), watch::error::RecvError>, watch::Receiver<T>)> + Send>>;
+
+async fn watch_changed<T>(
+    mut receiver: watch::Receiver<T>,
+) -> (Result<(), watch::error::RecvError>, watch::Receiver<T>) {
+    let result = receiver.changed().await;
+    (result, receiver)
+}
+
+/// Adapts a watch channel receiver to the event stream's input stream.
+struct WatchStream<T> {
+    // `None` once the channel was closed
+    changed: Option<WatchChanged<T>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Stream for WatchStream<T> {
+    type Item = Result<T, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let changed = match &mut self.changed {
+            Some(changed) => changed,
+            None => return Poll::Ready(None),
+        };
+        let (result, receiver) = ready!(changed.as_mut().poll(cx));
+        if result.is_err() {
+            self.changed = None;
+            return Poll::Ready(None);
+        }
+        let value = receiver.borrow().clone();
+        self.changed = Some(Box::pin(watch_changed(receiver)));
+        Poll::Ready(Some(Ok(value)))
     }
 }
 
@@ -43,6 +396,8 @@ where
     fn from(stream: S) -> Self {
         EventStreamInput {
             input_stream: Box::pin(stream),
+            end_handle: EndHandle::default(),
+            buffer: Default::default(),
         }
     }
 }
@@ -51,15 +406,100 @@ where
 /// message marshaller and signer implementations.
 ///
 /// This will yield an `Err(SdkError::ConstructionFailure)` if a message can't be
-/// marshalled into an Event Stream frame, (e.g., if the message payload was too large).
+/// marshalled into an Event Stream frame, (e.g., if the message payload was too large). When
+/// this happens to an event, the failure is an [`EventStreamConstructionError`] that identifies
+/// the event and the step that failed.
+///
+/// Each message is handled inside a `TRACE` level `event_stream.message` span that records the
+/// index of the event in the input stream, the message's type and payload size, with nested
+/// `event_stream.marshall`, `event_stream.sign`, and `event_stream.write` spans around each step.
+/// The end frame gets an `event_stream.end_frame` span.
+///
+/// Events are only taken from the input stream once the signer is ready to sign them, as
+/// reported by [`SignMessage::poll_ready`], so signers can refresh credentials asynchronously.
+///
+/// Frames that were yielded but not yet sent by the HTTP client count against a buffer budget; once
+/// it is exhausted, no more events are taken from the input stream until frames are sent. See
+/// [`with_max_buffered_bytes`](MessageStreamAdapter::with_max_buffered_bytes).
+///
+/// Frames can be observed as they are sent with a [`FrameInspector`]; see
+/// [`with_frame_inspector`](MessageStreamAdapter::with_frame_inspector).
+///
+/// While the input stream is idle, heartbeat frames can be sent to keep the connection alive; see
+/// [`with_heartbeat`](MessageStreamAdapter::with_heartbeat).
+///
+/// Once the input stream ends, a final signed empty frame produced by
+/// [`SignMessage::sign_empty`](SignMessage::sign_empty) is emitted by default, unless the signer
+/// returns `None`. See [`with_end_frame`](MessageStreamAdapter::with_end_frame) to disable it.
+/// The stream can also be ended before the input stream ends with an [`EndHandle`], or
+/// terminated early with a [`CloseHandle`].
+///
+/// The adapter is fused: once it yielded an error or its end frame, it is
+/// [terminated](MessageStreamAdapter::is_terminated), drops the input stream, and every later poll
+/// returns `None`. The only exception is the end frame of a [half-close](EndHandle), after which
+/// the body stays open until the input stream ends.
 pub struct MessageStreamAdapter<T, E> {
     marshaller: Box<dyn MarshallMessage<Input = T> + Send + Sync>,
     signer: Box<dyn SignMessage + Send + Sync>,
-    stream: Pin<Box<dyn Stream<Item = Result<T, BoxError>> + Send>>,
+    // `None` once the input stream ended, or the adapter was terminated
+    stream: Option<InputStream<T>>,
+    emit_end_frame: bool,
     end_signal_sent: bool,
+    end_handle: EndHandle,
+    max_frame_size: usize,
+    heartbeat: Option<Heartbeat>,
+    frame_inspector: Option<Box<dyn FrameInspector + Send + Sync>>,
+    metrics: Option<EventStreamMetrics>,
+    decorators: Vec<Box<dyn MessageDecorator + Send + Sync>>,
+    decorate_end_frame: bool,
+    // When the adapter was first polled, if it has metrics to measure the time to the first frame
+    first_polled: Option<Instant>,
+    #[cfg(feature = "event-stream-compression")]
+    payload_compression: Option<super::PayloadCompression>,
+    max_batch_bytes: usize,
+    buffer: Arc<BufferState>,
+    max_buffered_frames: usize,
+    max_buffered_bytes: usize,
+    next_event_index: u64,
+    // Set once the adapter has nothing left to yield
+    terminated: bool,
+    // An error or end frame that ended a batch, to be yielded on the next poll
+    deferred: Option<Result<Bytes, SdkError<E>>>,
     _phantom: PhantomData<E>,
 }
 
+/// Sends heartbeat frames while the input stream is idle
+struct Heartbeat {
+    interval: Duration,
+    message: fn() -> Message,
+    // Created on first use, since creating a timer requires a Tokio runtime
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Heartbeat {
+    /// Restarts the idle period, e.g. after an event was sent
+    fn reset(&mut self) {
+        if let Some(sleep) = &mut self.sleep {
+            sleep.as_mut().reset(Instant::now() + self.interval);
+        }
+    }
+
+    /// Returns `Ready` once the input stream has been idle for a whole interval
+    fn poll_elapsed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let interval = self.interval;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(interval)));
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.reset();
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 impl<T, E> Unpin for MessageStreamAdapter<T, E> {}
 
 impl<T, E> MessageStreamAdapter<T, E>
@@ -74,59 +514,900 @@ where
         MessageStreamAdapter {
             marshaller: Box::new(marshaller),
             signer: Box::new(signer),
-            stream,
+            stream: Some(stream),
+            emit_end_frame: true,
             end_signal_sent: false,
+            end_handle: EndHandle::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            heartbeat: None,
+            frame_inspector: None,
+            metrics: None,
+            decorators: Vec::new(),
+            decorate_end_frame: false,
+            first_polled: None,
+            #[cfg(feature = "event-stream-compression")]
+            payload_compression: None,
+            max_batch_bytes: 0,
+            buffer: Default::default(),
+            max_buffered_frames: usize::MAX,
+            max_buffered_bytes: usize::MAX,
+            next_event_index: 0,
+            terminated: false,
+            deferred: None,
             _phantom: Default::default(),
         }
     }
+
+    /// Sets whether a signed empty end frame is emitted after the input stream ends (default: `true`).
+    ///
+    /// When disabled, [`SignMessage::sign_empty`](SignMessage::sign_empty) is never called and the
+    /// adapter ends as soon as the input stream does. This is intended for protocols that signal the
+    /// end of the event stream by closing the request body rather than with an empty frame.
+    pub fn with_end_frame(mut self, emit_end_frame: bool) -> Self {
+        self.emit_end_frame = emit_end_frame;
+        self
+    }
+
+    /// Sets the largest frame, in bytes, that a marshalled message may be written to before it is
+    /// signed (default: 24 MiB, the limit of the event stream wire format).
+    ///
+    /// Messages that are too large yield an `SdkError::ConstructionFailure` caused by a
+    /// [`FrameTooLargeError`] rather than being sent.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Batches frames of events that are ready at the same time into a single chunk of up to
+    /// `max_batch_bytes` bytes (default: `0`, every frame is yielded on its own).
+    ///
+    /// For high-rate event streams, this reduces the number of chunks handed to the HTTP client, and
+    /// with it the number of writes. Each event is still marshalled and signed into its own frame,
+    /// so receivers are unaffected. The adapter never waits for more events to fill a batch, and a
+    /// single frame larger than `max_batch_bytes` is yielded on its own.
+    pub fn with_max_batch_bytes(mut self, max_batch_bytes: usize) -> Self {
+        self.max_batch_bytes = max_batch_bytes;
+        self
+    }
+
+    /// Limits the number of chunks that were yielded but not yet sent by the HTTP client (default:
+    /// unlimited). A batch of frames (see [`with_max_batch_bytes`](Self::with_max_batch_bytes))
+    /// counts as a single chunk.
+    pub fn with_max_buffered_frames(mut self, max_buffered_frames: usize) -> Self {
+        self.max_buffered_frames = max_buffered_frames;
+        self
+    }
+
+    /// Limits the number of bytes of frames that were yielded but not yet sent by the HTTP client
+    /// (default: unlimited).
+    ///
+    /// A chunk counts as buffered until the HTTP client drops it, which it does once the chunk was
+    /// written to the connection. While the limit is reached, the adapter stops taking events from
+    /// the input stream, so a producer feeding it through [`EventStreamInput::channel`] waits in
+    /// [`send`](EventStreamInputHandle::send) instead of outrunning the network. A chunk is always
+    /// yielded when nothing is buffered, even if it is larger than the limit.
+    pub fn with_max_buffered_bytes(mut self, max_buffered_bytes: usize) -> Self {
+        self.max_buffered_bytes = max_buffered_bytes;
+        self
+    }
+
+    /// Calls `inspector` with every frame once it was signed and written (default: none).
+    ///
+    /// Inspectors only get shared references to the messages and frames, so they can't change what
+    /// is sent. See [`TracingFrameInspector`] to log frames.
+    pub fn with_frame_inspector(
+        mut self,
+        inspector: impl FrameInspector + Send + Sync + 'static,
+    ) -> Self {
+        self.frame_inspector = Some(Box::new(inspector));
+        self
+    }
+
+    /// Gzips the payloads of marshalled messages before they are signed (default: disabled).
+    ///
+    /// Compressed messages carry a `:content-encoding` header of `gzip`, which a [`Receiver`]
+    /// (built with the same feature) uses to decompress them before unmarshalling. Only enable
+    /// this for services that accept compressed event payloads.
+    ///
+    /// [`Receiver`]: crate::event_stream::Receiver
+    #[cfg(feature = "event-stream-compression")]
+    pub fn with_payload_compression(mut self, compression: super::PayloadCompression) -> Self {
+        self.payload_compression = Some(compression);
+        self
+    }
+
+    /// Adds a decorator that every message goes through after it was marshalled, and before it
+    /// is signed (default: none). Decorators run in the order they were added.
+    ///
+    /// Since the signed frame wraps the decorated message, the signature covers the headers added
+    /// by decorators. Decorators also run for heartbeats and, if enabled with
+    /// [`with_decorated_end_frame`](Self::with_decorated_end_frame), for the end frame. See
+    /// [`TimestampDecorator`] to timestamp every frame.
+    pub fn with_message_decorator(
+        mut self,
+        decorator: impl MessageDecorator + Send + Sync + 'static,
+    ) -> Self {
+        self.decorators.push(Box::new(decorator));
+        self
+    }
+
+    /// Sets whether the end frame goes through the decorators (default: `false`).
+    ///
+    /// The end frame has an empty payload, so it has no message to wrap: it is produced signed by
+    /// [`SignMessage::sign_empty`], and decorated _after_ it was signed. Its decorated headers
+    /// therefore aren't covered by its signature, which is why this must be opted into.
+    pub fn with_decorated_end_frame(mut self, decorate_end_frame: bool) -> Self {
+        self.decorate_end_frame = decorate_end_frame;
+        self
+    }
+
+    /// Counts the frames sent, and the messages that couldn't be signed, in `metrics` (default:
+    /// none).
+    pub fn with_metrics(mut self, metrics: EventStreamMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sends a signed heartbeat frame whenever the input stream has had nothing to send for
+    /// `interval` (default: disabled).
+    ///
+    /// Intermediaries may tear down long-lived connections that stay idle. Heartbeats keep them
+    /// alive while waiting for events; `message` creates the (unsigned) heartbeat message
+    /// expected by the service. No heartbeats are sent while events are flowing, or once the
+    /// event stream has ended. This requires a Tokio runtime with the time driver enabled.
+    pub fn with_heartbeat(mut self, interval: Duration, message: fn() -> Message) -> Self {
+        self.heartbeat = Some(Heartbeat {
+            interval,
+            message,
+            sleep: None,
+        });
+        self
+    }
+}
+
+impl<T, E> MessageStreamAdapter<T, E>
+where
+    E: StdError + Send + Sync + 'static,
+{
+    /// Returns a handle that [ends](EndHandle::end) this event stream without closing it.
+    pub fn end_handle(&self) -> EndHandle {
+        self.end_handle.clone()
+    }
+
+    /// Returns a handle that [closes](CloseHandle::close) or [aborts](CloseHandle::abort) this
+    /// event stream.
+    pub fn close_handle(&self) -> CloseHandle {
+        self.end_handle.close_handle()
+    }
+
+    /// Returns the number of bytes of frames that were yielded but not yet sent by the HTTP client.
+    ///
+    /// Frames are only counted while a buffer limit is set, with
+    /// [`with_max_buffered_frames`](Self::with_max_buffered_frames) or
+    /// [`with_max_buffered_bytes`](Self::with_max_buffered_bytes).
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.bytes()
+    }
+
+    /// Returns `true` once the adapter has yielded its last chunk, i.e. after it yielded an error
+    /// or the end frame, or was closed. Polling a terminated adapter always returns `None`.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Stops the adapter, dropping the input stream along with anything left to yield
+    fn terminate(&mut self) {
+        self.terminated = true;
+        self.deferred = None;
+        self.stream = None;
+    }
+
+    /// Polls the input stream, dropping it once it has ended
+    fn poll_input(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, BoxError>>> {
+        let next = match &mut self.stream {
+            Some(stream) => ready!(stream.as_mut().poll_next(cx)),
+            None => None,
+        };
+        if next.is_none() {
+            self.stream = None;
+        }
+        Poll::Ready(next)
+    }
+
+    /// Returns `true` if a limit was set on the chunks yielded but not yet sent. Otherwise, they
+    /// aren't tracked at all.
+    fn limits_buffer(&self) -> bool {
+        self.max_buffered_frames != usize::MAX || self.max_buffered_bytes != usize::MAX
+    }
+
+    /// Returns `Ready` once the buffer has room for another chunk
+    fn poll_buffer_capacity(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.limits_buffer() {
+            return Poll::Ready(());
+        }
+        self.buffer.register(cx.waker());
+        if self.buffer.frames.load(Ordering::Acquire) >= self.max_buffered_frames
+            || self.buffer.bytes() >= self.max_buffered_bytes
+        {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+
+    fn end_frame(&mut self) -> Option<Result<Bytes, SdkError<E>>> {
+        if !self.emit_end_frame || self.end_signal_sent {
+            return None;
+        }
+        self.end_signal_sent = true;
+        let _enter = trace_span!("event_stream.end_frame").entered();
+        let message = {
+            let _enter = trace_span!("event_stream.sign").entered();
+            match self.signer.sign_empty()? {
+                Ok(message) => message,
+                Err(err) => {
+                    self.record_signing_failure();
+                    return Some(Err(SdkError::ConstructionFailure(err)));
+                }
+            }
+        };
+        let message = if self.decorate_end_frame {
+            self.decorate(message)
+        } else {
+            message
+        };
+        let frame =
+            write_message(&message).map_err(|err| SdkError::ConstructionFailure(Box::new(err)));
+        if let Ok(frame) = &frame {
+            self.frame_written(&message, frame);
+        }
+        Some(frame)
+    }
+
+    /// Marshalls, signs, and writes a single event into a frame.
+    #[allow(clippy::result_large_err)]
+    fn encode(&mut self, message_result: Result<T, BoxError>) -> Result<Bytes, SdkError<E>> {
+        let event_index = self.next_event_index;
+        self.next_event_index += 1;
+        let failed = |phase: ConstructionPhase| {
+            move |source: BoxError| {
+                SdkError::ConstructionFailure(Box::new(EventStreamConstructionError {
+                    event_index,
+                    phase,
+                    source,
+                }))
+            }
+        };
+        let span = trace_span!(
+            "event_stream.message",
+            event_index,
+            message_type = Empty,
+            payload_size = Empty
+        );
+        let _enter = span.enter();
+        let message = {
+            let _enter = trace_span!("event_stream.marshall").entered();
+            self.marshaller
+                .marshall(message_result.map_err(failed(ConstructionPhase::Marshall))?)
+                .map_err(|err| failed(ConstructionPhase::Marshall)(Box::new(err)))?
+        };
+        let message = self.decorate(message);
+        if let Some(message_type) = message_type(&message) {
+            span.record("message_type", message_type);
+        }
+        span.record("payload_size", message.payload().len());
+        #[cfg(feature = "event-stream-compression")]
+        let message = match &self.payload_compression {
+            Some(compression) => compression
+                .compress(message)
+                .map_err(|err| failed(ConstructionPhase::Compress)(Box::new(err)))?,
+            None => message,
+        };
+        let size = frame_size(&message);
+        if size > self.max_frame_size {
+            let err = FrameTooLargeError {
+                size,
+                limit: self.max_frame_size,
+            };
+            return Err(failed(ConstructionPhase::Serialize)(Box::new(err)));
+        }
+        // Only inspectors need the message once it has been signed
+        let unsigned = self.frame_inspector.as_ref().map(|_| message.clone());
+        let message = {
+            let _enter = trace_span!("event_stream.sign").entered();
+            match self.signer.sign(message) {
+                Ok(message) => message,
+                Err(err) => {
+                    self.record_signing_failure();
+                    return Err(failed(ConstructionPhase::Sign)(err));
+                }
+            }
+        };
+        let frame = write_message(&message)
+            .map_err(|err| failed(ConstructionPhase::Serialize)(Box::new(err)))?;
+        self.frame_written(unsigned.as_ref().unwrap_or(&message), &frame);
+        Ok(frame)
+    }
+
+    /// Appends the frames of events that are already available to `first`, up to
+    /// `max_batch_bytes`, without waiting for more events.
+    ///
+    /// An error, or the end of the input stream, ends the batch; the resulting error or end frame
+    /// is deferred to the next poll so that it is yielded after the frames that preceded it.
+    fn batch_ready_frames(&mut self, first: Bytes, cx: &mut Context<'_>) -> Bytes {
+        if first.len() >= self.max_batch_bytes {
+            return first;
+        }
+        let mut batch = BytesMut::from(&first[..]);
+        while batch.len() < self.max_batch_bytes
+            && !self.end_handle.is_ended()
+            && !self.end_handle.is_closed()
+        {
+            match self.poll_signer_ready(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => {
+                    self.deferred = Some(Err(err));
+                    break;
+                }
+                Poll::Pending => break,
+            }
+            match self.poll_input(cx) {
+                Poll::Ready(Some(message_result)) => match self.encode(message_result) {
+                    Ok(frame) => batch.extend_from_slice(&frame),
+                    Err(err) => {
+                        self.deferred = Some(Err(err));
+                        break;
+                    }
+                },
+                Poll::Ready(None) => {
+                    self.deferred = self.end_frame();
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+        batch.freeze()
+    }
+
+    fn decorate(&self, message: Message) -> Message {
+        self.decorators
+            .iter()
+            .fold(message, |message, decorator| decorator.decorate(message))
+    }
+
+    /// Hands a frame that was written to the inspector and metrics, if any
+    fn frame_written(&self, message: &Message, frame: &Bytes) {
+        if let Some(inspector) = &self.frame_inspector {
+            inspector.inspect(message, frame);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_frame_sent(frame.len());
+        }
+    }
+
+    fn record_signing_failure(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_signing_failure();
+        }
+    }
+
+    /// Polls the signer until it is able to sign the next frame.
+    fn poll_signer_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SdkError<E>>> {
+        self.signer
+            .poll_ready(cx)
+            .map_err(|err| SdkError::ConstructionFailure(err))
+    }
+
+    fn poll_heartbeat(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, SdkError<E>>>> {
+        let heartbeat = match &mut self.heartbeat {
+            Some(heartbeat) => heartbeat,
+            None => return Poll::Pending,
+        };
+        if heartbeat.poll_elapsed(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let unsigned = (heartbeat.message)();
+        let unsigned = self.decorate(unsigned);
+        let _enter = trace_span!("event_stream.heartbeat").entered();
+        let message = {
+            let _enter = trace_span!("event_stream.sign").entered();
+            match self.signer.sign(unsigned.clone()) {
+                Ok(message) => message,
+                Err(err) => {
+                    self.record_signing_failure();
+                    return Poll::Ready(Some(Err(SdkError::ConstructionFailure(err))));
+                }
+            }
+        };
+        let frame =
+            write_message(&message).map_err(|err| SdkError::ConstructionFailure(Box::new(err)));
+        if let Ok(frame) = &frame {
+            self.frame_written(&unsigned, frame);
+        }
+        Poll::Ready(Some(frame))
+    }
+}
+
+impl<T, E> Stream for MessageStreamAdapter<T, E>
+where
+    E: StdError + Send + Sync + 'static,
+{
+    type Item = Result<Bytes, SdkError<E>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.end_handle.register(cx.waker());
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        if let Some(err) = self.end_handle.take_abort_error() {
+            self.terminate();
+            return Poll::Ready(Some(Err(SdkError::ConstructionFailure(err))));
+        }
+        if self.metrics.is_some() && self.first_polled.is_none() {
+            self.first_polled = Some(Instant::now());
+        }
+        ready!(self.poll_buffer_capacity(cx));
+        let chunk = ready!(self.poll_chunk(cx));
+        // Nothing follows an error, or the input stream having ended with nothing left deferred
+        let input_ended = self.stream.is_none() && self.deferred.is_none();
+        Poll::Ready(match chunk {
+            Some(Ok(chunk)) => {
+                if input_ended {
+                    self.terminate();
+                }
+                if let (Some(metrics), Some(first_polled)) = (&self.metrics, self.first_polled) {
+                    metrics.record_first_frame_sent(first_polled.elapsed());
+                }
+                if self.limits_buffer() {
+                    Some(Ok(self.buffer.track(chunk)))
+                } else {
+                    Some(Ok(chunk))
+                }
+            }
+            other => {
+                self.terminate();
+                other
+            }
+        })
+    }
+}
+
+impl<T, E> MessageStreamAdapter<T, E>
+where
+    E: StdError + Send + Sync + 'static,
+{
+    /// Produces the next chunk to yield
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, SdkError<E>>>> {
+        if let Some(deferred) = self.deferred.take() {
+            return Poll::Ready(Some(deferred));
+        }
+        if self.end_handle.is_closed() {
+            if self.emit_end_frame && !self.end_signal_sent {
+                ready!(self.poll_signer_ready(cx))?;
+            }
+            // Nothing is taken from the input stream once closed
+            self.stream = None;
+            return Poll::Ready(self.end_frame());
+        }
+        if self.end_handle.is_ended() {
+            if self.emit_end_frame && !self.end_signal_sent {
+                ready!(self.poll_signer_ready(cx))?;
+            }
+            if let Some(end_frame) = self.end_frame() {
+                return Poll::Ready(Some(end_frame));
+            }
+            // Half-closed: no more events are sent, but the body stays open until the input
+            // stream ends.
+            return match self.poll_input(cx) {
+                Poll::Ready(Some(_)) => Poll::Ready(Some(Err(SdkError::ConstructionFailure(
+                    "an event was sent after the event stream was ended".into(),
+                )))),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            };
+        }
+        // Events are only taken from the input stream once they can be signed
+        ready!(self.poll_signer_ready(cx))?;
+        match self.poll_input(cx) {
+            Poll::Ready(message_option) => {
+                if let Some(heartbeat) = &mut self.heartbeat {
+                    heartbeat.reset();
+                }
+                if let Some(message_result) = message_option {
+                    let frame = self.encode(message_result)?;
+                    Poll::Ready(Some(Ok(self.batch_ready_frames(frame, cx))))
+                } else {
+                    Poll::Ready(self.end_frame())
+                }
+            }
+            Poll::Pending => self.poll_heartbeat(cx),
+        }
+    }
+}
+
+/// Adds to the messages sent by a [`MessageStreamAdapter`], e.g. headers required on every frame.
+///
+/// This is implemented for closures taking the same arguments as
+/// [`decorate`](MessageDecorator::decorate).
+pub trait MessageDecorator {
+    /// Called with each message after it was marshalled, and before it is signed.
+    fn decorate(&self, message: Message) -> Message;
+}
+
+impl<F> MessageDecorator for F
+where
+    F: Fn(Message) -> Message,
+{
+    fn decorate(&self, message: Message) -> Message {
+        self(message)
+    }
+}
+
+/// A [`MessageDecorator`] that adds a `:client-timestamp` timestamp header, holding the time the
+/// message was decorated, to every message.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimestampDecorator;
+
+impl TimestampDecorator {
+    /// Creates a new `TimestampDecorator`
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MessageDecorator for TimestampDecorator {
+    fn decorate(&self, message: Message) -> Message {
+        message.add_header(Header::new(
+            ":client-timestamp",
+            HeaderValue::Timestamp(DateTime::from(SystemTime::now())),
+        ))
+    }
+}
+
+/// Observes the frames sent by a [`MessageStreamAdapter`].
+///
+/// This is implemented for closures taking the same arguments as
+/// [`inspect`](FrameInspector::inspect).
+pub trait FrameInspector {
+    /// Called with each message as it was marshalled, before it was signed, and with the signed
+    /// frame it is sent in.
+    ///
+    /// Since the end frame is produced signed by [`SignMessage::sign_empty`], it is inspected
+    /// with the signed end message.
+    fn inspect(&self, message: &Message, frame: &Bytes);
+}
+
+impl<F> FrameInspector for F
+where
+    F: Fn(&Message, &Bytes),
+{
+    fn inspect(&self, message: &Message, frame: &Bytes) {
+        self(message, frame)
+    }
+}
+
+/// A [`FrameInspector`] that logs every frame at `DEBUG` level, with the type and payload size
+/// of its message, and the size of the frame.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingFrameInspector;
+
+impl TracingFrameInspector {
+    /// Creates a new `TracingFrameInspector`
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl FrameInspector for TracingFrameInspector {
+    fn inspect(&self, message: &Message, frame: &Bytes) {
+        debug!(
+            message_type = message_type(message).unwrap_or_default(),
+            payload_size = message.payload().len(),
+            frame_size = frame.len(),
+            "sending event stream frame"
+        );
+    }
+}
+
+/// Ends an event stream without closing the request body it is sent over.
+///
+/// Calling [`end`](EndHandle::end) tells the other side that no more events will be sent (a
+/// _half-close_), while keeping the connection open to receive events from the other side of a
+/// bidirectional stream. Once ended:
+///
+/// - the signed empty end frame produced by [`SignMessage::sign_empty`] is sent, unless it was
+///   disabled with [`MessageStreamAdapter::with_end_frame`]. The end frame is only ever sent once,
+///   whether the stream was ended with this handle or by the input stream ending;
+/// - events that are still pending in, or later sent to, the input stream are not sent; the
+///   adapter yields an error for the first of them instead, and terminates;
+/// - the request body ends when the input stream does.
+#[derive(Clone, Debug, Default)]
+pub struct EndHandle {
+    state: Arc<EndState>,
+}
+
+/// State shared by an adapter and its [`EndHandle`]s and [`CloseHandle`]s
+#[derive(Debug, Default)]
+struct EndState {
+    ended: AtomicBool,
+    closed: AtomicBool,
+    abort_error: Mutex<Option<BoxError>>,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl EndState {
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl EndHandle {
+    /// Ends the event stream.
+    pub fn end(&self) {
+        self.state.ended.store(true, Ordering::Release);
+        self.state.wake();
+    }
+
+    /// Returns `true` if the event stream was ended.
+    pub fn is_ended(&self) -> bool {
+        self.state.ended.load(Ordering::Acquire)
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut registered = self.state.waker.lock().unwrap();
+        if !matches!(&*registered, Some(registered) if registered.will_wake(waker)) {
+            *registered = Some(waker.clone());
+        }
+    }
+}
+
+/// Terminates an event stream early, either cleanly or with an error.
+///
+/// - [`close`](CloseHandle::close) sends the signed empty end frame, unless it was disabled with
+///   [`MessageStreamAdapter::with_end_frame`] or was already sent, and then ends the request body;
+/// - [`abort`](CloseHandle::abort) makes the adapter yield the given error, which fails the
+///   request, and then end.
+///
+/// In both cases, the input stream is no longer polled: events that are still pending in it are
+/// dropped without being sent. Unlike an [`EndHandle`], this doesn't wait for the input stream to
+/// end.
+#[derive(Clone, Debug)]
+pub struct CloseHandle {
+    state: Arc<EndState>,
+}
+
+impl CloseHandle {
+    /// Closes the event stream after sending the end frame.
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::Release);
+        self.state.wake();
+    }
+
+    /// Aborts the event stream with an error.
+    ///
+    /// The error is ignored if the adapter already ended after an earlier close or abort.
+    pub fn abort(&self, err: impl Into<BoxError>) {
+        *self.state.abort_error.lock().unwrap() = Some(err.into());
+        self.close();
+    }
+
+    /// Returns `true` if the event stream was closed or aborted.
+    pub fn is_closed(&self) -> bool {
+        self.state.closed.load(Ordering::Acquire)
+    }
+}
+
+impl EndHandle {
+    fn close_handle(&self) -> CloseHandle {
+        CloseHandle {
+            state: self.state.clone(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.closed.load(Ordering::Acquire)
+    }
+
+    fn take_abort_error(&self) -> Option<BoxError> {
+        self.state.abort_error.lock().unwrap().take()
+    }
+}
+
+/// Chunks yielded by an adapter that the HTTP client hasn't sent yet
+#[derive(Debug, Default)]
+struct BufferState {
+    frames: AtomicUsize,
+    bytes: AtomicUsize,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl BufferState {
+    fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Acquire)
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut registered = self.waker.lock().unwrap();
+        if !matches!(&*registered, Some(registered) if registered.will_wake(waker)) {
+            *registered = Some(waker.clone());
+        }
+    }
+
+    /// Counts `chunk` as buffered until the returned `Bytes`, and all of its clones, are dropped
+    fn track(self: &Arc<Self>, chunk: Bytes) -> Bytes {
+        self.frames.fetch_add(1, Ordering::AcqRel);
+        self.bytes.fetch_add(chunk.len(), Ordering::AcqRel);
+        Bytes::from_owner(BufferedChunk {
+            chunk,
+            state: self.clone(),
+        })
+    }
+}
+
+/// Releases its space in the buffer when dropped
+struct BufferedChunk {
+    chunk: Bytes,
+    state: Arc<BufferState>,
+}
+
+impl AsRef<[u8]> for BufferedChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.chunk
+    }
+}
+
+impl Drop for BufferedChunk {
+    fn drop(&mut self) {
+        self.state.frames.fetch_sub(1, Ordering::AcqRel);
+        self.state
+            .bytes
+            .fetch_sub(self.chunk.len(), Ordering::AcqRel);
+        if let Some(waker) = self.state.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The largest frame allowed by the event stream wire format, including its headers
+const DEFAULT_MAX_FRAME_SIZE: usize = 24 * 1024 * 1024;
+
+/// Returns the size of the frame that `message` would be written to
+fn frame_size(message: &Message) -> usize {
+    // The prelude (total length, headers length, and prelude CRC) and the message CRC
+    const FRAMING_SIZE: usize = 4 * 4;
+    let mut headers = Vec::new();
+    // Headers that can't be written fail later on, when the message is written
+    let _ = write_headers_to(message.headers(), &mut headers);
+    FRAMING_SIZE + headers.len() + message.payload().len()
+}
+
+/// Error yielded by a [`MessageStreamAdapter`] when a marshalled message is too large to be sent.
+#[derive(Debug)]
+pub struct FrameTooLargeError {
+    size: usize,
+    limit: usize,
+}
+
+impl FrameTooLargeError {
+    /// Returns the size of the frame that the message would have been written to, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the largest frame size allowed, in bytes.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for FrameTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event stream message of {} bytes exceeds the maximum frame size of {} bytes",
+            self.size, self.limit
+        )
+    }
+}
+
+impl StdError for FrameTooLargeError {}
+
+/// The step of sending an event that failed, see [`EventStreamConstructionError`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstructionPhase {
+    /// The input stream yielded an error instead of the event, or the event couldn't be
+    /// marshalled into a message.
+    Marshall,
+    /// The message payload couldn't be compressed.
+    Compress,
+    /// The message couldn't be signed.
+    Sign,
+    /// The message couldn't be written to a frame, e.g. because it was too large.
+    Serialize,
+}
+
+impl fmt::Display for ConstructionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstructionPhase::Marshall => write!(f, "marshall"),
+            ConstructionPhase::Compress => write!(f, "compress"),
+            ConstructionPhase::Sign => write!(f, "sign"),
+            ConstructionPhase::Serialize => write!(f, "serialize"),
+        }
+    }
+}
+
+/// Error yielded by a [`MessageStreamAdapter`], as an `SdkError::ConstructionFailure`, when an
+/// event can't be sent.
+///
+/// The underlying error is available as its [`source`](StdError::source).
+#[derive(Debug)]
+pub struct EventStreamConstructionError {
+    event_index: u64,
+    phase: ConstructionPhase,
+    source: BoxError,
+}
+
+impl EventStreamConstructionError {
+    /// Returns the index of the event in the input stream, starting from 0.
+    pub fn event_index(&self) -> u64 {
+        self.event_index
+    }
+
+    /// Returns the step of sending the event that failed.
+    pub fn phase(&self) -> ConstructionPhase {
+        self.phase
+    }
+}
+
+impl fmt::Display for EventStreamConstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to {} event {} of the event stream",
+            self.phase, self.event_index
+        )
+    }
 }
 
-impl<T, E> Stream for MessageStreamAdapter<T, E>
-where
-    E: StdError + Send + Sync + 'static,
-{
-    type Item = Result<Bytes, SdkError<E>>;
-
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.stream.as_mut().poll_next(cx) {
-            Poll::Ready(message_option) => {
-                if let Some(message_result) = message_option {
-                    let message_result =
-                        message_result.map_err(|err| SdkError::ConstructionFailure(err));
-                    let message = self
-                        .marshaller
-                        .marshall(message_result?)
-                        .map_err(|err| SdkError::ConstructionFailure(Box::new(err)))?;
-                    let message = self
-                        .signer
-                        .sign(message)
-                        .map_err(|err| SdkError::ConstructionFailure(err))?;
-                    let mut buffer = Vec::new();
-                    message
-                        .write_to(&mut buffer)
-                        .map_err(|err| SdkError::ConstructionFailure(Box::new(err)))?;
-                    Poll::Ready(Some(Ok(Bytes::from(buffer))))
-                } else if !self.end_signal_sent {
-                    self.end_signal_sent = true;
-                    let mut buffer = Vec::new();
-                    self.signer
-                        .sign_empty()
-                        .map_err(|err| SdkError::ConstructionFailure(err))?
-                        .write_to(&mut buffer)
-                        .map_err(|err| SdkError::ConstructionFailure(Box::new(err)))?;
-                    Poll::Ready(Some(Ok(Bytes::from(buffer))))
-                } else {
-                    Poll::Ready(None)
-                }
-            }
-            Poll::Pending => Poll::Pending,
-        }
+impl StdError for EventStreamConstructionError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source.as_ref() as _)
     }
 }
 
+/// Returns the `:event-type` (or failing that, `:message-type`) header of a marshalled message
+fn message_type(message: &Message) -> Option<&str> {
+    [":event-type", ":message-type"].iter().find_map(|name| {
+        message
+            .headers()
+            .iter()
+            .find(|header| header.name().as_str() == *name)
+            .and_then(|header| header.value().as_string().ok())
+            .map(|value| value.as_str())
+    })
+}
+
+fn write_message(message: &Message) -> Result<Bytes, EventStreamError> {
+    let _enter = trace_span!("event_stream.write").entered();
+    let mut buffer = Vec::new();
+    message.write_to(&mut buffer)?;
+    trace!(frame_size = buffer.len(), "wrote event stream frame");
+    Ok(Bytes::from(buffer))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::MarshallMessage;
+    use super::{
+        BroadcastLagPolicy, BroadcastLaggedError, ConstructionPhase, EventStreamConstructionError,
+        FrameTooLargeError, MarshallMessage, TimestampDecorator, TracingFrameInspector,
+    };
+    use crate::event_stream::test_util::FrameRecorder;
     use crate::event_stream::{EventStreamInput, MessageStreamAdapter};
     use crate::result::SdkError;
     use async_stream::stream;
@@ -137,7 +1418,12 @@ mod tests {
     use bytes::Bytes;
     use futures_core::Stream;
     use futures_util::stream::StreamExt;
+    use futures_util::FutureExt;
     use std::error::Error as StdError;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use tokio::time::Instant;
 
     #[derive(Debug)]
     struct FakeError;
@@ -148,7 +1434,7 @@ mod tests {
     }
     impl StdError for FakeError {}
 
-    #[derive(Debug, Eq, PartialEq)]
+    #[derive(Clone, Debug, Eq, PartialEq)]
     struct TestMessage(String);
 
     #[derive(Debug)]
@@ -179,8 +1465,10 @@ mod tests {
             Ok(Message::new(buffer).add_header(Header::new("signed", HeaderValue::Bool(true))))
         }
 
-        fn sign_empty(&mut self) -> Result<Message, SignMessageError> {
-            Ok(Message::new(&b""[..]).add_header(Header::new("signed", HeaderValue::Bool(true))))
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            Some(Ok(
+                Message::new(&b""[..]).add_header(Header::new("signed", HeaderValue::Bool(true)))
+            ))
         }
     }
 
@@ -198,7 +1486,7 @@ mod tests {
         let stream = stream! {
             yield Ok(TestMessage("test".into()));
         };
-        let mut adapter =
+        let adapter =
             check_compatible_with_hyper_wrap_stream(MessageStreamAdapter::<
                 TestMessage,
                 TestServiceError,
@@ -206,23 +1494,823 @@ mod tests {
                 Marshaller, TestSigner, Box::pin(stream)
             ));
 
+        let mut recorder = FrameRecorder::new().with_signature_header("signed");
+        recorder.record(adapter).await.unwrap();
+        assert_eq!(2, recorder.len());
+        recorder.assert_signed();
+        assert_eq!(&b"test"[..], &recorder.payload_of(0)[..]);
+        assert!(recorder.event(1).is_none());
+    }
+
+    /// Numbers the messages it signs, like a signer chaining each signature to the previous one
+    #[derive(Debug, Default)]
+    struct SequenceSigner {
+        sequence: i32,
+    }
+    impl SignMessage for SequenceSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            let mut buffer = Vec::new();
+            message.write_to(&mut buffer).unwrap();
+            self.sequence += 1;
+            Ok(Message::new(buffer)
+                .add_header(Header::new("sequence", HeaderValue::Int32(self.sequence))))
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn event_stream_input_initial_message() {
+        let stream = stream! {
+            yield Ok(TestMessage("first".into()));
+        };
+        let input =
+            EventStreamInput::from(stream).with_initial_message(TestMessage("initial".into()));
+        let frames: Vec<_> = input
+            .into_body_stream::<TestServiceError>(Marshaller, SequenceSigner::default())
+            .map(|frame| frame.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(2, frames.len());
+        for (frame, (sequence, payload)) in frames.iter().zip([(1, "initial"), (2, "first")]) {
+            let sent = Message::read_from(&mut frame.clone()).unwrap();
+            assert_eq!(&HeaderValue::Int32(sequence), sent.headers()[0].value());
+            let inner = Message::read_from(&mut (&sent.payload()[..])).unwrap();
+            assert_eq!(payload.as_bytes(), &inner.payload()[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_without_end_frame() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_end_frame(false);
+
+        let mut sent_bytes = adapter.next().await.unwrap().unwrap();
+        let sent = Message::read_from(&mut sent_bytes).unwrap();
+        let inner = Message::read_from(&mut (&sent.payload()[..])).unwrap();
+        assert_eq!(&b"test"[..], &inner.payload()[..]);
+
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn message_stream_adapter_traces_each_message() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        );
+        while let Some(frame) = adapter.next().await {
+            frame.unwrap();
+        }
+
+        assert!(logs_contain(
+            "event_stream.message{event_index=0 payload_size=4}"
+        ));
+        assert!(logs_contain("event_stream.end_frame:event_stream.write"));
+        logs_assert(|lines: &[&str]| {
+            match lines
+                .iter()
+                .filter(|line| line.contains("wrote event stream frame"))
+                .count()
+            {
+                2 => Ok(()),
+                n => Err(format!("expected 2 frames to be written, found {}", n)),
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_frame_inspector() {
+        let inspected = Arc::new(Mutex::new(Vec::new()));
+        let stream = stream! {
+            yield Ok(TestMessage("one".into()));
+            yield Ok(TestMessage("two".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_frame_inspector({
+            let inspected = inspected.clone();
+            move |message: &Message, frame: &Bytes| {
+                inspected
+                    .lock()
+                    .unwrap()
+                    .push((message.payload().clone(), frame.clone()))
+            }
+        });
+        let mut sent = Vec::new();
+        while let Some(frame) = adapter.next().await {
+            sent.push(frame.unwrap());
+        }
+
+        let inspected = inspected.lock().unwrap();
+        let (messages, frames): (Vec<_>, Vec<_>) = inspected.iter().cloned().unzip();
+        assert_eq!(sent, frames);
+        // Events are inspected before signing; the end frame is signed
+        assert_eq!(&messages[..2], &[Bytes::from("one"), Bytes::from("two")]);
+        assert!(messages[2].is_empty());
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn tracing_frame_inspector() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_end_frame(false)
+        .with_frame_inspector(TracingFrameInspector::new());
+        let frame = adapter.next().await.unwrap().unwrap();
+
+        assert!(logs_contain(&format!(
+            "sending event stream frame message_type=\"\" payload_size=4 frame_size={}",
+            frame.len()
+        )));
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_half_close() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+            // Keep the input stream open, as a bidirectional stream would
+            futures_util::future::pending::<()>().await;
+        };
+        let input = EventStreamInput::from(stream);
+        let end_handle = input.end_handle();
+        let mut adapter = input.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+
         let mut sent_bytes = adapter.next().await.unwrap().unwrap();
         let sent = Message::read_from(&mut sent_bytes).unwrap();
-        assert_eq!("signed", sent.headers()[0].name().as_str());
-        assert_eq!(&HeaderValue::Bool(true), sent.headers()[0].value());
         let inner = Message::read_from(&mut (&sent.payload()[..])).unwrap();
         assert_eq!(&b"test"[..], &inner.payload()[..]);
 
+        end_handle.end();
         let mut end_signal_bytes = adapter.next().await.unwrap().unwrap();
         let end_signal = Message::read_from(&mut end_signal_bytes).unwrap();
         assert_eq!("signed", end_signal.headers()[0].name().as_str());
-        assert_eq!(&HeaderValue::Bool(true), end_signal.headers()[0].value());
         assert_eq!(0, end_signal.payload().len());
+
+        // The body stays open, without sending another end frame
+        assert!(adapter.next().now_or_never().is_none());
+        assert!(adapter.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn event_stream_input_from_channel() {
+        let (handle, input) = EventStreamInput::channel(1);
+        let mut adapter = input.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+
+        let sender = tokio::spawn(async move {
+            for event in ["one", "two", "three"] {
+                handle.send(TestMessage(event.into())).await.unwrap();
+            }
+            handle.close();
+            handle.send(TestMessage("four".into())).await.unwrap_err()
+        });
+
+        let mut payloads = Vec::new();
+        while let Some(frame) = adapter.next().await {
+            let mut frame = frame.unwrap();
+            let sent = Message::read_from(&mut frame).unwrap();
+            assert_eq!("signed", sent.headers()[0].name().as_str());
+            payloads.push(sent.payload().clone());
+        }
+        let inner_payloads: Vec<_> = payloads[..3]
+            .iter()
+            .map(|payload| {
+                Message::read_from(&mut &payload[..])
+                    .unwrap()
+                    .payload()
+                    .clone()
+            })
+            .collect();
+        assert_eq!(vec!["one", "two", "three"], inner_payloads);
+        // The signed end frame comes last
+        assert_eq!(4, payloads.len());
+        assert!(payloads[3].is_empty());
+        assert_eq!(
+            TestMessage("four".into()),
+            sender.await.unwrap().into_inner()
+        );
+    }
+
+    #[tokio::test]
+    async fn event_stream_input_from_broadcast_lagging() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(2);
+        let synthesized = EventStreamInput::from_broadcast(
+            sender.subscribe(),
+            BroadcastLagPolicy::Synthesize(|skipped| TestMessage(format!("skipped {}", skipped))),
+        );
+        let failing = EventStreamInput::from_broadcast(receiver, BroadcastLagPolicy::Error);
+        // The consumers are too slow to keep up with the channel
+        for n in 0..5 {
+            sender.send(TestMessage(n.to_string())).unwrap();
+        }
+        drop(sender);
+
+        let frames: Vec<_> = synthesized
+            .into_body_stream::<TestServiceError>(Marshaller, TestSigner)
+            .with_end_frame(false)
+            .map(|frame| unsign(frame.unwrap()))
+            .collect()
+            .await;
+        assert_eq!(vec!["skipped 3", "3", "4"], frames);
+
+        let mut adapter = failing.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+        let err = match adapter.next().await.unwrap() {
+            Err(SdkError::ConstructionFailure(err)) => err,
+            other => panic!("expected a construction failure, got {:?}", other),
+        };
+        let err = err
+            .source()
+            .unwrap()
+            .downcast_ref::<BroadcastLaggedError>()
+            .unwrap();
+        assert_eq!(3, err.skipped());
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn event_stream_input_from_watch() {
+        let (sender, receiver) = tokio::sync::watch::channel(TestMessage("initial".into()));
+        let mut adapter = EventStreamInput::from_watch(receiver)
+            .into_body_stream::<TestServiceError>(Marshaller, TestSigner)
+            .with_end_frame(false);
+
+        assert!(adapter.next().now_or_never().is_none());
+        sender.send(TestMessage("first".into())).unwrap();
+        assert_eq!("first", unsign(adapter.next().await.unwrap().unwrap()));
+        // Changes that weren't sent yet are coalesced
+        sender.send(TestMessage("second".into())).unwrap();
+        sender.send(TestMessage("third".into())).unwrap();
+        assert_eq!("third", unsign(adapter.next().await.unwrap().unwrap()));
+        drop(sender);
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_buffer_budget() {
+        let (handle, input) = EventStreamInput::channel(1);
+        let mut adapter = input
+            .into_body_stream::<TestServiceError>(Marshaller, TestSigner)
+            .with_max_buffered_bytes(1);
+        // Chunks given to a connection that never writes them to the network stay buffered
+        let mut stalled_connection = Vec::new();
+
+        handle.send(TestMessage("one".into())).await.unwrap();
+        stalled_connection.push(adapter.next().await.unwrap().unwrap());
+        assert_eq!(stalled_connection[0].len(), handle.buffered_bytes());
+        assert_eq!(handle.buffered_bytes(), adapter.buffered_bytes());
+
+        // The budget is exhausted, so the adapter no longer takes events from the channel...
+        handle.send(TestMessage("two".into())).await.unwrap();
+        assert!(adapter.next().now_or_never().is_none());
+        // ...and the producer waits once the channel is full
+        let mut send = Box::pin(handle.send(TestMessage("three".into())));
+        assert!((&mut send).now_or_never().is_none());
+
+        // Once the connection sends what it was given, the producer resumes
+        stalled_connection.clear();
+        assert_eq!(0, handle.buffered_bytes());
+        assert_eq!("two", unsign(adapter.next().await.unwrap().unwrap()));
+        send.await.unwrap();
+        assert_eq!(0, handle.buffered_bytes());
+        assert_eq!("three", unsign(adapter.next().await.unwrap().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_untracked_without_buffer_limit() {
+        let (handle, input) = EventStreamInput::channel(1);
+        let mut adapter = input.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+
+        handle.send(TestMessage("one".into())).await.unwrap();
+        let chunk = adapter.next().await.unwrap().unwrap();
+        assert_eq!(0, adapter.buffered_bytes());
+        assert_eq!("one", unsign(chunk));
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_max_frame_size() {
+        let stream = stream! {
+            // Frames are 16 bytes larger than their payload
+            yield Ok(TestMessage("test".into()));
+            yield Ok(TestMessage("tests".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_max_frame_size(20);
+
+        assert!(adapter.next().await.unwrap().is_ok());
+        let err = match adapter.next().await.unwrap() {
+            Err(SdkError::ConstructionFailure(err)) => err,
+            other => panic!("expected a construction failure, got {:?}", other),
+        };
+        let err = err.downcast_ref::<EventStreamConstructionError>().unwrap();
+        assert_eq!(
+            (1, ConstructionPhase::Serialize),
+            (err.event_index(), err.phase())
+        );
+        let err = err
+            .source()
+            .unwrap()
+            .downcast_ref::<FrameTooLargeError>()
+            .unwrap();
+        assert_eq!((21, 20), (err.size(), err.limit()));
+        assert_eq!(
+            "event stream message of 21 bytes exceeds the maximum frame size of 20 bytes",
+            err.to_string()
+        );
+    }
+
+    fn heartbeat() -> Message {
+        Message::new(&b""[..]).add_header(Header::new(
+            ":event-type",
+            HeaderValue::String("heartbeat".into()),
+        ))
+    }
+
+    /// Returns the `:event-type` of a heartbeat, or the payload of an event, signed by `TestSigner`
+    fn unsign(frame: Bytes) -> String {
+        let sent = Message::read_from(&mut &frame[..]).unwrap();
+        let inner = Message::read_from(&mut &sent.payload()[..]).unwrap();
+        match inner.headers().first() {
+            Some(header) => header.value().as_string().unwrap().as_str().to_string(),
+            None => String::from_utf8(inner.payload().to_vec()).unwrap(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn message_stream_adapter_heartbeats() {
+        let (handle, input) = EventStreamInput::channel(8);
+        let mut adapter = input
+            .into_body_stream::<TestServiceError>(Marshaller, TestSigner)
+            .with_heartbeat(Duration::from_secs(1), heartbeat);
+
+        // Idle: time is auto-advanced until the heartbeat is due
+        let start = Instant::now();
+        assert_eq!("heartbeat", unsign(adapter.next().await.unwrap().unwrap()));
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+
+        // Events flowing more often than the interval suppress heartbeats
+        for event in ["one", "two", "three"] {
+            tokio::time::advance(Duration::from_millis(600)).await;
+            handle.send(TestMessage(event.into())).await.unwrap();
+            assert_eq!(event, unsign(adapter.next().await.unwrap().unwrap()));
+        }
+
+        let start = Instant::now();
+        assert_eq!("heartbeat", unsign(adapter.next().await.unwrap().unwrap()));
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+
+        handle.close();
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        let end_frame = Message::read_from(&mut &end_frame[..]).unwrap();
+        assert!(end_frame.payload().is_empty());
+        assert!(adapter.next().await.is_none());
+    }
+
+    /// Splits a chunk yielded by the adapter into the payloads of the events it contains
+    fn unbatch(mut chunk: Bytes) -> Vec<String> {
+        let mut frames = Vec::new();
+        while !chunk.is_empty() {
+            let mut rest = chunk.clone();
+            Message::read_from(&mut rest).unwrap();
+            let frame = chunk.split_to(chunk.len() - rest.len());
+            frames.push(unsign(frame));
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_batches_ready_frames() {
+        let events = ["a", "b", "c", "d", "e"];
+        let stream = futures_util::stream::iter(events.map(|event| Ok(TestMessage(event.into()))));
+        let single_frame_size = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(futures_util::stream::iter(vec![Ok(TestMessage(
+                "a".into(),
+            ))])),
+        )
+        .next()
+        .await
+        .unwrap()
+        .unwrap()
+        .len();
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_max_batch_bytes(single_frame_size * 2);
+
+        assert_eq!(
+            vec!["a", "b"],
+            unbatch(adapter.next().await.unwrap().unwrap())
+        );
+        assert_eq!(
+            vec!["c", "d"],
+            unbatch(adapter.next().await.unwrap().unwrap())
+        );
+        // The input stream ends while batching `e`: the end frame follows in its own chunk
+        assert_eq!(vec!["e"], unbatch(adapter.next().await.unwrap().unwrap()));
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        let end_frame = Message::read_from(&mut &end_frame[..]).unwrap();
+        assert!(end_frame.payload().is_empty());
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_batch_defers_errors() {
+        let stream = stream! {
+            yield Ok(TestMessage("a".into()));
+            yield Ok(TestMessage("b".into()));
+            yield Err(Box::new(FakeError) as Box<dyn StdError + Send + Sync>);
+            yield Ok(TestMessage("c".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_max_batch_bytes(usize::MAX);
+
+        assert_eq!(
+            vec!["a", "b"],
+            unbatch(adapter.next().await.unwrap().unwrap())
+        );
+        assert!(adapter.next().await.unwrap().is_err());
+        // Nothing is sent after an error
+        assert!(adapter.next().await.is_none());
+    }
+
+    /// A `TestSigner` that isn't ready to sign until it has been polled `not_ready_polls` times
+    #[derive(Debug)]
+    struct SlowSigner {
+        not_ready_polls: usize,
+    }
+    impl SignMessage for SlowSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            assert_eq!(0, self.not_ready_polls, "signed before being ready");
+            TestSigner.sign(message)
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            assert_eq!(0, self.not_ready_polls, "signed before being ready");
+            TestSigner.sign_empty()
+        }
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SignMessageError>> {
+            if self.not_ready_polls == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            self.not_ready_polls -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_waits_for_signer() {
+        let (handle, input) = EventStreamInput::channel(1);
+        handle.send(TestMessage("test".into())).await.unwrap();
+        let mut adapter = input
+            .into_body_stream::<TestServiceError>(Marshaller, SlowSigner { not_ready_polls: 2 });
+
+        assert!(adapter.next().now_or_never().is_none());
+        // The event stays in the input stream while the signer isn't ready
+        assert!(handle
+            .send(TestMessage("full".into()))
+            .now_or_never()
+            .is_none());
+        assert_eq!("test", unsign(adapter.next().await.unwrap().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_close() {
+        let (handle, input) = EventStreamInput::channel(8);
+        let close_handle = input.close_handle();
+        let mut adapter = input.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+
+        handle.send(TestMessage("one".into())).await.unwrap();
+        assert_eq!("one", unsign(adapter.next().await.unwrap().unwrap()));
+
+        // Queued events are dropped
+        handle.send(TestMessage("two".into())).await.unwrap();
+        handle.send(TestMessage("three".into())).await.unwrap();
+        close_handle.close();
+        assert!(close_handle.is_closed());
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        let end_frame = Message::read_from(&mut &end_frame[..]).unwrap();
+        assert_eq!("signed", end_frame.headers()[0].name().as_str());
+        assert!(end_frame.payload().is_empty());
+        assert!(adapter.next().await.is_none());
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_abort() {
+        let (handle, input) = EventStreamInput::channel(8);
+        let close_handle = input.close_handle();
+        let mut adapter = input.into_body_stream::<TestServiceError>(Marshaller, TestSigner);
+
+        handle.send(TestMessage("one".into())).await.unwrap();
+        handle.send(TestMessage("two".into())).await.unwrap();
+        assert_eq!("one", unsign(adapter.next().await.unwrap().unwrap()));
+
+        close_handle.abort("the producer failed");
+        match adapter.next().await.unwrap() {
+            Err(SdkError::ConstructionFailure(err)) => {
+                assert_eq!("the producer failed", err.to_string())
+            }
+            other => panic!("expected a construction failure, got {:?}", other),
+        }
+        // Neither the queued event nor the end frame are sent
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[derive(Debug)]
+    struct EndFrameSigner(Option<Result<(), &'static str>>);
+    impl SignMessage for EndFrameSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            TestSigner.sign(message)
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            self.0.map(|result| {
+                result
+                    .map(|_| Message::new(&b""[..]))
+                    .map_err(SignMessageError::from)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_signer_skips_end_frame() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            EndFrameSigner(None),
+            Box::pin(stream),
+        );
+
+        assert_eq!("test", unsign(adapter.next().await.unwrap().unwrap()));
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_end_frame_signing_failure() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            EndFrameSigner(Some(Err("no credentials"))),
+            Box::pin(stream),
+        );
+
+        assert_eq!("test", unsign(adapter.next().await.unwrap().unwrap()));
+        match adapter.next().await.unwrap() {
+            Err(SdkError::ConstructionFailure(err)) => {
+                assert_eq!("no credentials", err.to_string())
+            }
+            other => panic!("expected a construction failure, got {:?}", other),
+        }
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_message_decorators() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_decorated_end_frame(true)
+        .with_message_decorator(TimestampDecorator::new())
+        .with_message_decorator(|message: Message| {
+            message.add_header(Header::new(
+                "correlation-id",
+                HeaderValue::String("abc".into()),
+            ))
+        });
+        let decorated = |message: &Message| {
+            let names: Vec<_> = message
+                .headers()
+                .iter()
+                .map(|header| header.name().as_str())
+                .collect();
+            assert!(
+                names.ends_with(&[":client-timestamp", "correlation-id"]),
+                "{:?}",
+                names
+            );
+            assert!(message.headers()[names.len() - 2]
+                .value()
+                .as_timestamp()
+                .is_ok());
+        };
+
+        // The decorated headers are signed along with the rest of the message
+        let frame = adapter.next().await.unwrap().unwrap();
+        let sent = Message::read_from(&mut &frame[..]).unwrap();
+        let inner = Message::read_from(&mut &sent.payload()[..]).unwrap();
+        decorated(&inner);
+        assert_eq!(&b"test"[..], &inner.payload()[..]);
+
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        let end_frame = Message::read_from(&mut &end_frame[..]).unwrap();
+        decorated(&end_frame);
+        assert!(end_frame.payload().is_empty());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_undecorated_end_frame() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        )
+        .with_message_decorator(TimestampDecorator::new());
+
+        // The end frame isn't decorated by default
+        assert!(adapter.next().await.unwrap().is_ok());
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        let end_frame = Message::read_from(&mut &end_frame[..]).unwrap();
+        assert_eq!(1, end_frame.headers().len());
+        assert_eq!("signed", end_frame.headers()[0].name().as_str());
+    }
+
+    /// Signs messages with a hash of the wrapped message, including all its headers
+    #[derive(Debug)]
+    struct DigestSigner;
+    impl DigestSigner {
+        fn signature(payload: &[u8]) -> i64 {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            payload.hash(&mut hasher);
+            hasher.finish() as i64
+        }
+    }
+    impl SignMessage for DigestSigner {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            let mut buffer = Vec::new();
+            message.write_to(&mut buffer).unwrap();
+            let signature = Self::signature(&buffer);
+            Ok(Message::new(buffer)
+                .add_header(Header::new("signature", HeaderValue::Int64(signature))))
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_signature_covers_decorated_headers() {
+        let stream = stream! {
+            yield Ok(TestMessage("test".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            DigestSigner,
+            Box::pin(stream),
+        )
+        .with_message_decorator(|message: Message| {
+            message.add_header(Header::new(
+                "correlation-id",
+                HeaderValue::String("abc".into()),
+            ))
+        });
+
+        let frame = adapter.next().await.unwrap().unwrap();
+        let sent = Message::read_from(&mut &frame[..]).unwrap();
+        let signature = sent.headers()[0].value().as_int64().unwrap();
+        assert_eq!(DigestSigner::signature(sent.payload()), signature);
+
+        // Without the decorated header, the message doesn't match the signature
+        let inner = Message::read_from(&mut &sent.payload()[..]).unwrap();
+        assert_eq!(
+            "correlation-id",
+            inner.headers().last().unwrap().name().as_str()
+        );
+        let mut undecorated = Message::new(inner.payload().clone());
+        for header in &inner.headers()[..inner.headers().len() - 1] {
+            undecorated = undecorated.add_header(header.clone());
+        }
+        let mut buffer = Vec::new();
+        undecorated.write_to(&mut buffer).unwrap();
+        assert_ne!(DigestSigner::signature(&buffer), signature);
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_terminates_after_error() {
+        // Held by the input stream until it is dropped
+        let held = Arc::new(());
+        let in_stream = held.clone();
+        let stream = stream! {
+            let _held = in_stream;
+            yield Ok(TestMessage("a".into()));
+            yield Err(Box::new(FakeError) as Box<dyn StdError + Send + Sync>);
+            yield Ok(TestMessage("b".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        );
+
+        assert_eq!("a", unsign(adapter.next().await.unwrap().unwrap()));
+        assert!(!adapter.is_terminated());
+        assert!(adapter.next().await.unwrap().is_err());
+        assert!(adapter.is_terminated());
+        assert_eq!(1, Arc::strong_count(&held), "the input stream was dropped");
+        assert!(adapter.next().await.is_none());
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_no_end_frame_after_source_error() {
+        let stream = stream! {
+            yield Ok(TestMessage("a".into()));
+            yield Err(Box::new(FakeError) as Box<dyn StdError + Send + Sync>);
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            EndFrameSigner(Some(Err("the end frame must not be signed"))),
+            Box::pin(stream),
+        );
+
+        assert_eq!("a", unsign(adapter.next().await.unwrap().unwrap()));
+        match adapter.next().await.unwrap() {
+            Err(SdkError::ConstructionFailure(err)) => {
+                let err = err.downcast_ref::<EventStreamConstructionError>().unwrap();
+                assert_eq!(ConstructionPhase::Marshall, err.phase());
+            }
+            other => panic!("expected a construction failure, got {:?}", other),
+        }
+        // The input stream has ended, but no end frame follows the error
+        assert!(adapter.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn message_stream_adapter_terminates_after_end_frame() {
+        let stream = stream! {
+            yield Ok(TestMessage("a".into()));
+        };
+        let mut adapter = MessageStreamAdapter::<TestMessage, TestServiceError>::new(
+            Marshaller,
+            TestSigner,
+            Box::pin(stream),
+        );
+
+        assert_eq!("a", unsign(adapter.next().await.unwrap().unwrap()));
+        let end_frame = adapter.next().await.unwrap().unwrap();
+        assert!(Message::read_from(&mut &end_frame[..])
+            .unwrap()
+            .payload()
+            .is_empty());
+        assert!(adapter.is_terminated());
+        assert!(adapter.next().await.is_none());
+        assert!(adapter.next().await.is_none());
     }
 
     #[tokio::test]
     async fn message_stream_adapter_construction_failure() {
         let stream = stream! {
+            yield Ok(TestMessage("test".into()));
             yield Err(EventStreamError::InvalidMessageLength.into());
         };
         let mut adapter =
@@ -233,12 +2321,24 @@ mod tests {
                 Marshaller, TestSigner, Box::pin(stream)
             ));
 
+        assert!(adapter.next().await.unwrap().is_ok());
         let result = adapter.next().await.unwrap();
         assert!(result.is_err());
+        let err = match result.err().unwrap() {
+            SdkError::ConstructionFailure(err) => err,
+            other => panic!("expected a construction failure, got {:?}", other),
+        };
+        let err = err.downcast_ref::<EventStreamConstructionError>().unwrap();
+        assert_eq!(1, err.event_index());
+        assert_eq!(ConstructionPhase::Marshall, err.phase());
         assert!(matches!(
-            result.err().unwrap(),
-            SdkError::ConstructionFailure(_)
+            err.source().unwrap().downcast_ref::<EventStreamError>(),
+            Some(EventStreamError::InvalidMessageLength)
         ));
+        assert_eq!(
+            "failed to marshall event 1 of the event stream",
+            err.to_string()
+        );
     }
 
     // Verify the developer experience for this compiles
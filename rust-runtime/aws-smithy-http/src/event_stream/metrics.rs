@@ -0,0 +1,255 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Counters describing the traffic of an event stream.
+///
+/// Attach a handle to a [`MessageStreamAdapter`](super::MessageStreamAdapter) with
+/// [`with_metrics`](super::MessageStreamAdapter::with_metrics), and/or to a
+/// [`Receiver`](super::Receiver) with [`with_metrics`](super::Receiver::with_metrics). Clones of
+/// the handle share the same counters, so the application can keep one to read them (or export
+/// them) while the event stream is running. Counters are updated without locking.
+#[derive(Clone, Debug, Default)]
+pub struct EventStreamMetrics {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    frames_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    frames_received: AtomicU64,
+    bytes_received: AtomicU64,
+    signing_failures: AtomicU64,
+    unmarshalling_failures: AtomicU64,
+    // In nanoseconds, `0` until recorded
+    time_to_first_frame_sent: AtomicU64,
+    time_to_first_frame_received: AtomicU64,
+}
+
+impl EventStreamMetrics {
+    /// Creates a new handle with all counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of frames written to the request body, including end frames and heartbeats
+    pub fn frames_sent(&self) -> u64 {
+        self.counters.frames_sent.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes of the frames written to the request body
+    pub fn bytes_sent(&self) -> u64 {
+        self.counters.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// The number of frames decoded from the response body
+    pub fn frames_received(&self) -> u64 {
+        self.counters.frames_received.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes read from the response body
+    pub fn bytes_received(&self) -> u64 {
+        self.counters.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// The number of messages that couldn't be signed
+    pub fn signing_failures(&self) -> u64 {
+        self.counters.signing_failures.load(Ordering::Relaxed)
+    }
+
+    /// The number of received messages that couldn't be unmarshalled
+    pub fn unmarshalling_failures(&self) -> u64 {
+        self.counters.unmarshalling_failures.load(Ordering::Relaxed)
+    }
+
+    /// The time from the first poll of the adapter until it yielded its first frame, if it did
+    pub fn time_to_first_frame_sent(&self) -> Option<Duration> {
+        duration(&self.counters.time_to_first_frame_sent)
+    }
+
+    /// The time from the first call to receive a message until the first frame was decoded, if
+    /// one was
+    pub fn time_to_first_frame_received(&self) -> Option<Duration> {
+        duration(&self.counters.time_to_first_frame_received)
+    }
+
+    pub(crate) fn record_frame_sent(&self, size: usize) {
+        self.counters.frames_sent.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .bytes_sent
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_frame_received(&self) {
+        self.counters
+            .frames_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_bytes_received(&self, size: usize) {
+        self.counters
+            .bytes_received
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_signing_failure(&self) {
+        self.counters
+            .signing_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_unmarshalling_failure(&self) {
+        self.counters
+            .unmarshalling_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_first_frame_sent(&self, elapsed: Duration) {
+        record_once(&self.counters.time_to_first_frame_sent, elapsed);
+    }
+
+    pub(crate) fn record_first_frame_received(&self, elapsed: Duration) {
+        record_once(&self.counters.time_to_first_frame_received, elapsed);
+    }
+}
+
+fn duration(nanos: &AtomicU64) -> Option<Duration> {
+    match nanos.load(Ordering::Relaxed) {
+        0 => None,
+        nanos => Some(Duration::from_nanos(nanos)),
+    }
+}
+
+/// Records `elapsed` unless a duration was already recorded
+fn record_once(nanos: &AtomicU64, elapsed: Duration) {
+    // `0` means "not recorded", so the shortest recordable duration is 1ns
+    let elapsed = (elapsed.as_nanos() as u64).max(1);
+    let _ = nanos.compare_exchange(0, elapsed, Ordering::Relaxed, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventStreamMetrics;
+    use crate::body::SdkBody;
+    use crate::event_stream::{MessageStreamAdapter, Receiver};
+    use async_stream::stream;
+    use aws_smithy_eventstream::error::Error as EventStreamError;
+    use aws_smithy_eventstream::frame::{
+        MarshallMessage, Message, SignMessage, SignMessageError, UnmarshallMessage,
+        UnmarshalledMessage,
+    };
+    use futures_util::StreamExt;
+
+    #[derive(Debug)]
+    struct TestError;
+    impl std::fmt::Display for TestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "TestError")
+        }
+    }
+    impl std::error::Error for TestError {}
+
+    #[derive(Debug)]
+    struct Marshaller;
+    impl MarshallMessage for Marshaller {
+        type Input = &'static str;
+
+        fn marshall(&self, input: Self::Input) -> Result<Message, EventStreamError> {
+            Ok(Message::new(input.as_bytes()))
+        }
+    }
+
+    /// Fails to sign messages with a `bad` payload
+    #[derive(Debug)]
+    struct Signer;
+    impl SignMessage for Signer {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            if &message.payload()[..] == b"bad" {
+                return Err("can't sign".into());
+            }
+            Ok(message)
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            Some(Ok(Message::new(&b""[..])))
+        }
+    }
+
+    /// Fails to unmarshall messages with a `bad` payload
+    #[derive(Debug)]
+    struct Unmarshaller;
+    impl UnmarshallMessage for Unmarshaller {
+        type Output = String;
+        type Error = TestError;
+
+        fn unmarshall(
+            &self,
+            message: &Message,
+        ) -> Result<UnmarshalledMessage<Self::Output, Self::Error>, EventStreamError> {
+            match &message.payload()[..] {
+                b"bad" => Err(EventStreamError::InvalidMessageLength),
+                payload => Ok(UnmarshalledMessage::Event(
+                    String::from_utf8(payload.to_vec()).unwrap(),
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_metrics() {
+        let metrics = EventStreamMetrics::new();
+        let stream = stream! {
+            yield Ok("a");
+            yield Ok("bb");
+            yield Ok("bad");
+        };
+        let mut adapter =
+            MessageStreamAdapter::<_, TestError>::new(Marshaller, Signer, Box::pin(stream))
+                .with_metrics(metrics.clone());
+        assert_eq!(None, metrics.time_to_first_frame_sent());
+
+        let mut sent_bytes = 0;
+        sent_bytes += adapter.next().await.unwrap().unwrap().len();
+        assert!(metrics.time_to_first_frame_sent().is_some());
+        sent_bytes += adapter.next().await.unwrap().unwrap().len();
+        assert!(adapter.next().await.unwrap().is_err());
+        assert!(adapter.next().await.is_none());
+
+        // Frames are 16 bytes larger than their payload
+        assert_eq!(16 * 2 + 3, sent_bytes);
+        assert_eq!(2, metrics.frames_sent());
+        assert_eq!(sent_bytes as u64, metrics.bytes_sent());
+        assert_eq!(1, metrics.signing_failures());
+        assert_eq!(0, metrics.frames_received());
+    }
+
+    #[tokio::test]
+    async fn receive_metrics() {
+        let mut body = Vec::new();
+        for payload in ["a", "bb", "bad"] {
+            Message::new(payload.as_bytes())
+                .write_to(&mut body)
+                .unwrap();
+        }
+        let body_len = body.len() as u64;
+        let metrics = EventStreamMetrics::new();
+        let mut receiver =
+            Receiver::new(Unmarshaller, SdkBody::from(body)).with_metrics(metrics.clone());
+
+        assert_eq!(Some("a".to_string()), receiver.recv().await.unwrap());
+        assert!(metrics.time_to_first_frame_received().is_some());
+        assert_eq!(Some("bb".to_string()), receiver.recv().await.unwrap());
+        assert!(receiver.recv().await.is_err());
+
+        assert_eq!(3, metrics.frames_received());
+        assert_eq!(body_len, metrics.bytes_received());
+        assert_eq!(1, metrics.unmarshalling_failures());
+        assert_eq!(0, metrics.frames_sent());
+    }
+}
@@ -3,8 +3,10 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use super::EventStreamMetrics;
 use crate::body::SdkBody;
 use crate::result::{ConnectorError, SdkError};
+use aws_smithy_eventstream::error::Error as EventStreamError;
 use aws_smithy_eventstream::frame::{
     DecodedFrame, Message, MessageFrameDecoder, UnmarshallMessage, UnmarshalledMessage,
 };
@@ -16,6 +18,8 @@ use std::error::Error as StdError;
 use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
+use std::time::Instant;
+use tracing::warn;
 
 /// Wrapper around SegmentedBuf that tracks the state of the stream.
 #[derive(Debug)]
@@ -116,6 +120,69 @@ impl fmt::Display for Error {
 
 impl StdError for Error {}
 
+/// An event whose `:event-type` isn't known to the unmarshaller, e.g. because the service added a
+/// new event after the client was generated.
+#[derive(Debug)]
+pub struct UnknownEvent {
+    event_type: String,
+    message: Message,
+}
+
+impl UnknownEvent {
+    /// Returns the `:event-type` of the event.
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    /// Returns the message the event was received in.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Consumes the `UnknownEvent`, returning the message the event was received in.
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+/// What a [`Receiver`] does with events whose `:event-type` isn't known to the unmarshaller.
+#[non_exhaustive]
+pub enum UnknownEventPolicy<T> {
+    /// Fail with an `SdkError::ResponseError`. This is the default.
+    Error,
+    /// Drop the event, log a warning, and receive the next one.
+    Skip,
+    /// Receive the event as a `T` created from the [`UnknownEvent`], e.g. the unknown variant of
+    /// the event union.
+    Yield(fn(UnknownEvent) -> T),
+}
+
+// Deriving this with `#[default]` would require a newer compiler than the MSRV
+#[allow(clippy::derivable_impls)]
+impl<T> Default for UnknownEventPolicy<T> {
+    fn default() -> Self {
+        Self::Error
+    }
+}
+
+impl<T> Clone for UnknownEventPolicy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UnknownEventPolicy<T> {}
+
+impl<T> fmt::Debug for UnknownEventPolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Skip => write!(f, "Skip"),
+            Self::Yield(_) => write!(f, "Yield"),
+        }
+    }
+}
+
 /// Receives Smithy-modeled messages out of an Event Stream.
 #[derive(Debug)]
 pub struct Receiver<T, E> {
@@ -128,6 +195,13 @@ pub struct Receiver<T, E> {
     /// initial response, then the message will be stored in `buffered_message` so that it can
     /// be returned with the next call of `recv()`.
     buffered_message: Option<Message>,
+    /// Whether the first message has been checked for being an initial response.
+    initial_response_checked: bool,
+    unknown_event_policy: UnknownEventPolicy<T>,
+    metrics: Option<EventStreamMetrics>,
+    /// When the first message was asked for, if there are metrics to measure the time to the
+    /// first frame
+    first_requested: Option<Instant>,
     _phantom: PhantomData<E>,
 }
 
@@ -143,11 +217,50 @@ impl<T, E> Receiver<T, E> {
             buffer: RecvBuf::Empty,
             body,
             buffered_message: None,
+            initial_response_checked: false,
+            unknown_event_policy: Default::default(),
+            metrics: None,
+            first_requested: None,
             _phantom: Default::default(),
         }
     }
 
+    /// Sets what to do with events whose `:event-type` isn't known to the unmarshaller (default:
+    /// [`UnknownEventPolicy::Error`]).
+    ///
+    /// The unmarshaller reports these events with an
+    /// [`UnknownEventType`](aws_smithy_eventstream::error::Error::UnknownEventType) error.
+    pub fn with_unknown_event_policy(mut self, policy: UnknownEventPolicy<T>) -> Self {
+        self.unknown_event_policy = policy;
+        self
+    }
+
+    /// Counts the frames and bytes received, and the messages that couldn't be unmarshalled, in
+    /// `metrics` (default: none).
+    pub fn with_metrics(mut self, metrics: EventStreamMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn record_unmarshalling_failure(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_unmarshalling_failure();
+        }
+    }
+
+    /// Unmarshalls a message, returning `Ok(None)` if it was skipped.
     fn unmarshall(&self, message: Message) -> Result<Option<T>, SdkError<E, RawMessage>> {
+        #[cfg(feature = "event-stream-compression")]
+        let message = match super::compression::decompress(&message) {
+            Ok(decompressed) => decompressed.unwrap_or(message),
+            Err(err) => {
+                self.record_unmarshalling_failure();
+                return Err(SdkError::ResponseError {
+                    err: Box::new(err),
+                    raw: RawMessage::Decoded(message),
+                });
+            }
+        };
         match self.unmarshaller.unmarshall(&message) {
             Ok(unmarshalled) => match unmarshalled {
                 UnmarshalledMessage::Event(event) => Ok(Some(event)),
@@ -156,10 +269,32 @@ impl<T, E> Receiver<T, E> {
                     raw: RawMessage::Decoded(message),
                 }),
             },
-            Err(err) => Err(SdkError::ResponseError {
-                err: Box::new(err),
-                raw: RawMessage::Decoded(message),
-            }),
+            Err(EventStreamError::UnknownEventType(event_type)) => {
+                match self.unknown_event_policy {
+                    UnknownEventPolicy::Error => {
+                        self.record_unmarshalling_failure();
+                        Err(SdkError::ResponseError {
+                            err: Box::new(EventStreamError::UnknownEventType(event_type)),
+                            raw: RawMessage::Decoded(message),
+                        })
+                    }
+                    UnknownEventPolicy::Skip => {
+                        warn!(event_type = %event_type, "skipping event of unknown type");
+                        Ok(None)
+                    }
+                    UnknownEventPolicy::Yield(into_event) => Ok(Some(into_event(UnknownEvent {
+                        event_type,
+                        message,
+                    }))),
+                }
+            }
+            Err(err) => {
+                self.record_unmarshalling_failure();
+                Err(SdkError::ResponseError {
+                    err: Box::new(err),
+                    raw: RawMessage::Decoded(message),
+                })
+            }
         }
     }
 
@@ -173,6 +308,9 @@ impl<T, E> Receiver<T, E> {
                 .map_err(|err| SdkError::DispatchFailure(ConnectorError::io(err)))?;
             let buffer = mem::replace(&mut self.buffer, RecvBuf::Empty);
             if let Some(chunk) = next_chunk {
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_bytes_received(chunk.len());
+                }
                 self.buffer = buffer.with_partial(chunk);
             } else {
                 self.buffer = buffer.ended();
@@ -182,6 +320,9 @@ impl<T, E> Receiver<T, E> {
     }
 
     async fn next_message(&mut self) -> Result<Option<Message>, SdkError<E, RawMessage>> {
+        if self.metrics.is_some() && self.first_requested.is_none() {
+            self.first_requested = Some(Instant::now());
+        }
         while !self.buffer.is_eos() {
             if self.buffer.has_data() {
                 if let DecodedFrame::Complete(message) = self
@@ -192,6 +333,12 @@ impl<T, E> Receiver<T, E> {
                         raw: RawMessage::Invalid(None), // the buffer has been consumed
                     })?
                 {
+                    if let (Some(metrics), Some(first_requested)) =
+                        (&self.metrics, self.first_requested)
+                    {
+                        metrics.record_frame_received();
+                        metrics.record_first_frame_received(first_requested.elapsed());
+                    }
                     return Ok(Some(message));
                 }
             }
@@ -209,26 +356,21 @@ impl<T, E> Receiver<T, E> {
 
     /// Tries to receive the initial response message that has `:event-type` of `initial-response`.
     /// If a different event type is received, then it is buffered and `Ok(None)` is returned.
+    ///
+    /// Only the first message of the stream can be an initial response: once this has been
+    /// called, or once [`recv`](Receiver::recv) has been called, this always returns `Ok(None)`.
     #[doc(hidden)]
     pub async fn try_recv_initial(&mut self) -> Result<Option<Message>, SdkError<E, RawMessage>> {
+        if self.initial_response_checked {
+            return Ok(None);
+        }
+        self.initial_response_checked = true;
         if let Some(message) = self.next_message().await? {
-            if let Some(event_type) = message
-                .headers()
-                .iter()
-                .find(|h| h.name().as_str() == ":event-type")
-            {
-                if event_type
-                    .value()
-                    .as_string()
-                    .map(|s| s.as_str() == "initial-response")
-                    .unwrap_or(false)
-                {
-                    return Ok(Some(message));
-                }
-            } else {
-                // Buffer the message so that it can be returned by the next call to `recv()`
-                self.buffered_message = Some(message);
+            if is_initial_response(&message) {
+                return Ok(Some(message));
             }
+            // Buffer the message so that it can be returned by the next call to `recv()`
+            self.buffered_message = Some(message);
         }
         Ok(None)
     }
@@ -238,20 +380,36 @@ impl<T, E> Receiver<T, E> {
     /// `Err(SdkError::DispatchFailure)`. Service-modeled errors will be a part of the returned
     /// messages.
     pub async fn recv(&mut self) -> Result<Option<T>, SdkError<E, RawMessage>> {
-        if let Some(buffered) = self.buffered_message.take() {
-            return self.unmarshall(buffered);
-        }
-        if let Some(message) = self.next_message().await? {
-            self.unmarshall(message)
-        } else {
-            Ok(None)
+        // An initial response that wasn't received with `try_recv_initial()` isn't an event
+        self.try_recv_initial().await?;
+        loop {
+            let message = match self.buffered_message.take() {
+                Some(buffered) => buffered,
+                None => match self.next_message().await? {
+                    Some(message) => message,
+                    None => return Ok(None),
+                },
+            };
+            if let Some(event) = self.unmarshall(message)? {
+                return Ok(Some(event));
+            }
         }
     }
 }
 
+fn is_initial_response(message: &Message) -> bool {
+    message
+        .headers()
+        .iter()
+        .find(|h| h.name().as_str() == ":event-type")
+        .and_then(|event_type| event_type.value().as_string().ok())
+        .map(|event_type| event_type.as_str() == "initial-response")
+        .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Receiver, UnmarshallMessage};
+    use super::{Receiver, UnknownEvent, UnknownEventPolicy, UnmarshallMessage};
     use crate::body::SdkBody;
     use crate::result::SdkError;
     use aws_smithy_eventstream::error::Error as EventStreamError;
@@ -313,6 +471,101 @@ mod tests {
         }
     }
 
+    #[derive(Debug)]
+    enum TestEvent {
+        Greeting(String),
+        Unknown(UnknownEvent),
+    }
+
+    /// Only knows about `greeting` events
+    #[derive(Debug)]
+    struct GreetingUnmarshaller;
+    impl UnmarshallMessage for GreetingUnmarshaller {
+        type Output = TestEvent;
+        type Error = EventStreamError;
+
+        fn unmarshall(
+            &self,
+            message: &Message,
+        ) -> Result<UnmarshalledMessage<Self::Output, Self::Error>, EventStreamError> {
+            let event_type = message
+                .headers()
+                .iter()
+                .find(|header| header.name().as_str() == ":event-type")
+                .map(|header| header.value().as_string().unwrap().as_str().to_string())
+                .unwrap();
+            match event_type.as_str() {
+                "greeting" => Ok(UnmarshalledMessage::Event(TestEvent::Greeting(
+                    std::str::from_utf8(&message.payload()[..]).unwrap().into(),
+                ))),
+                _ => Err(EventStreamError::UnknownEventType(event_type)),
+            }
+        }
+    }
+
+    fn encode_event(event_type: &str, payload: &str) -> Bytes {
+        let mut buffer = Vec::new();
+        Message::new(Bytes::copy_from_slice(payload.as_bytes()))
+            .add_header(Header::new(
+                ":event-type",
+                HeaderValue::String(event_type.to_string().into()),
+            ))
+            .write_to(&mut buffer)
+            .unwrap();
+        buffer.into()
+    }
+
+    fn greeting_receiver() -> Receiver<TestEvent, EventStreamError> {
+        let chunks: Vec<Result<_, IOError>> = vec![
+            Ok(encode_event("greeting", "hello")),
+            Ok(encode_event("farewell", "bye")),
+            Ok(encode_event("greeting", "again")),
+        ];
+        let chunk_stream = futures_util::stream::iter(chunks);
+        let body = SdkBody::from(Body::wrap_stream(chunk_stream));
+        Receiver::new(GreetingUnmarshaller, body)
+    }
+
+    fn expect_greeting(event: Option<TestEvent>) -> String {
+        match event {
+            Some(TestEvent::Greeting(greeting)) => greeting,
+            other => panic!("expected a greeting, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn receive_unknown_event_fails_by_default() {
+        let mut receiver = greeting_receiver();
+        assert_eq!("hello", expect_greeting(receiver.recv().await.unwrap()));
+        let err = receiver.recv().await.expect_err("unknown event");
+        assert!(matches!(err, SdkError::ResponseError { .. }));
+        assert_eq!("unrecognized :event-type: farewell", err.to_string());
+    }
+
+    #[tokio::test]
+    async fn receive_unknown_event_skip() {
+        let mut receiver = greeting_receiver().with_unknown_event_policy(UnknownEventPolicy::Skip);
+        assert_eq!("hello", expect_greeting(receiver.recv().await.unwrap()));
+        assert_eq!("again", expect_greeting(receiver.recv().await.unwrap()));
+        assert!(receiver.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn receive_unknown_event_yield() {
+        let mut receiver = greeting_receiver()
+            .with_unknown_event_policy(UnknownEventPolicy::Yield(TestEvent::Unknown));
+        assert_eq!("hello", expect_greeting(receiver.recv().await.unwrap()));
+        match receiver.recv().await.unwrap() {
+            Some(TestEvent::Unknown(unknown)) => {
+                assert_eq!("farewell", unknown.event_type());
+                assert_eq!(&b"bye"[..], &unknown.message().payload()[..]);
+            }
+            other => panic!("expected an unknown event, got {:?}", other),
+        }
+        assert_eq!("again", expect_greeting(receiver.recv().await.unwrap()));
+        assert!(receiver.recv().await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn receive_success() {
         let chunks: Vec<Result<_, IOError>> =
@@ -376,6 +629,48 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn receive_corrupted_frame() {
+        let frame = encode_message("one");
+        let mut corrupted = frame.to_vec();
+        // Flip a bit of the payload
+        corrupted[12] ^= 0x01;
+        let chunks: Vec<Result<_, IOError>> = vec![Ok(Bytes::from(corrupted))];
+        let chunk_stream = futures_util::stream::iter(chunks);
+        let body = SdkBody::from(Body::wrap_stream(chunk_stream));
+        let mut receiver = Receiver::<TestMessage, EventStreamError>::new(Unmarshaller, body);
+
+        let err = receiver
+            .recv()
+            .await
+            .expect_err("the message checksum doesn't match");
+        let message = err.to_string();
+        let mismatch = match err {
+            SdkError::ResponseError { err, .. } => {
+                match *err.downcast::<EventStreamError>().unwrap() {
+                    EventStreamError::MessageChecksumMismatch(mismatch) => mismatch,
+                    err => panic!("unexpected error: {}", err),
+                }
+            }
+            err => panic!("unexpected error: {}", err),
+        };
+        let frame_crc = u32::from_be_bytes(frame[frame.len() - 4..].try_into().unwrap());
+        assert_eq!(frame_crc, mismatch.actual_crc());
+        assert_ne!(frame_crc, mismatch.expected_crc());
+        assert_eq!(frame.len() as u32, mismatch.total_length());
+        assert_eq!(&frame[..12], &mismatch.frame_prefix()[..12]);
+        assert_eq!(b'o' ^ 0x01, mismatch.frame_prefix()[12]);
+        assert!(
+            message.starts_with(&format!(
+                "message checksum 0x{:X} didn't match expected checksum 0x{:X} (frame length: 19 bytes, first 19 bytes: 00 00 00 13 00 00 00 00",
+                mismatch.actual_crc(),
+                mismatch.expected_crc()
+            )),
+            "{}",
+            message
+        );
+    }
+
     #[tokio::test]
     async fn receive_last_chunk_has_multiple_messages() {
         let chunks: Vec<Result<_, IOError>> = vec![
@@ -524,6 +819,69 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn receive_initial_response_only_once() {
+        let chunks: Vec<Result<_, IOError>> = vec![
+            Ok(encode_initial_response()),
+            Ok(encode_initial_response()),
+            Ok(encode_message("one")),
+        ];
+        let chunk_stream = futures_util::stream::iter(chunks);
+        let body = SdkBody::from(Body::wrap_stream(chunk_stream));
+        let mut receiver = Receiver::<TestMessage, EventStreamError>::new(Unmarshaller, body);
+        assert!(receiver.try_recv_initial().await.unwrap().is_some());
+        assert!(receiver.try_recv_initial().await.unwrap().is_none());
+        // Only the first message can be an initial response
+        assert_eq!(
+            TestMessage("".into()),
+            receiver.recv().await.unwrap().unwrap()
+        );
+        assert_eq!(
+            TestMessage("one".into()),
+            receiver.recv().await.unwrap().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn receive_initial_response_buffers_other_event_types() {
+        let mut first = Vec::new();
+        Message::new(&b"one"[..])
+            .add_header(Header::new(
+                ":event-type",
+                HeaderValue::String("SomeEvent".into()),
+            ))
+            .write_to(&mut first)
+            .unwrap();
+        let chunks: Vec<Result<_, IOError>> = vec![Ok(first.into()), Ok(encode_message("two"))];
+        let chunk_stream = futures_util::stream::iter(chunks);
+        let body = SdkBody::from(Body::wrap_stream(chunk_stream));
+        let mut receiver = Receiver::<TestMessage, EventStreamError>::new(Unmarshaller, body);
+        assert!(receiver.try_recv_initial().await.unwrap().is_none());
+        assert_eq!(
+            TestMessage("one".into()),
+            receiver.recv().await.unwrap().unwrap()
+        );
+        assert_eq!(
+            TestMessage("two".into()),
+            receiver.recv().await.unwrap().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn receive_skips_unrequested_initial_response() {
+        let chunks: Vec<Result<_, IOError>> =
+            vec![Ok(encode_initial_response()), Ok(encode_message("one"))];
+        let chunk_stream = futures_util::stream::iter(chunks);
+        let body = SdkBody::from(Body::wrap_stream(chunk_stream));
+        let mut receiver = Receiver::<TestMessage, EventStreamError>::new(Unmarshaller, body);
+        assert_eq!(
+            TestMessage("one".into()),
+            receiver.recv().await.unwrap().unwrap()
+        );
+        assert!(receiver.try_recv_initial().await.unwrap().is_none());
+        assert!(receiver.recv().await.unwrap().is_none());
+    }
+
     fn assert_send<T: Send>() {}
 
     #[tokio::test]
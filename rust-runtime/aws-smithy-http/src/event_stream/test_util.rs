@@ -0,0 +1,288 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Utilities for testing event streams.
+//!
+//! [`FrameRecorder`] collects the frames written by an event stream, such as a
+//! [`MessageStreamAdapter`](super::MessageStreamAdapter), and makes assertions about them:
+//!
+//! ```no_run
+//! # async fn example<S>(adapter: S)
+//! # where S: futures_core::Stream<Item = Result<bytes::Bytes, std::io::Error>> {
+//! use aws_smithy_http::event_stream::test_util::FrameRecorder;
+//!
+//! let mut recorder = FrameRecorder::new();
+//! recorder.record(adapter).await.expect("no errors");
+//! recorder.assert_signed();
+//! recorder.assert_event_types(&["initial-request", "AudioEvent", "end"]);
+//! assert_eq!(b"audio", &recorder.payload_of(1)[..]);
+//! # }
+//! ```
+
+use aws_smithy_eventstream::frame::Message;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+/// Message header that marks a frame as signed, as written by the SigV4 event stream signer
+const CHUNK_SIGNATURE: &str = ":chunk-signature";
+const EVENT_TYPE: &str = ":event-type";
+
+/// Event type reported for the empty signed frame that ends a stream
+const END: &str = "end";
+
+/// Collects the frames of an event stream, for assertions in tests.
+///
+/// Signed frames wrap the message that was signed in their payload. The recorder unwraps them,
+/// so that [`assert_event_types`](FrameRecorder::assert_event_types) and
+/// [`payload_of`](FrameRecorder::payload_of) describe the event that was sent rather than its
+/// signature envelope.
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    frames: Vec<(Message, Bytes)>,
+    signature_header: String,
+}
+
+impl Default for FrameRecorder {
+    fn default() -> Self {
+        Self {
+            frames: Vec::new(),
+            signature_header: CHUNK_SIGNATURE.into(),
+        }
+    }
+}
+
+impl FrameRecorder {
+    /// Creates an empty recorder that recognizes signed frames by their `:chunk-signature` header
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recognize signed frames by the header `name` instead of `:chunk-signature`
+    pub fn with_signature_header(mut self, name: impl Into<String>) -> Self {
+        self.signature_header = name.into();
+        self
+    }
+
+    /// Records every frame of `stream` until it ends, or yields an error which is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a frame isn't a valid event stream message.
+    pub async fn record<S, B, E>(&mut self, stream: S) -> Result<(), E>
+    where
+        S: Stream<Item = Result<B, E>>,
+        B: Into<Bytes>,
+    {
+        let mut stream = Box::pin(stream);
+        while let Some(frame) = stream.next().await {
+            self.record_frame(frame?.into());
+        }
+        Ok(())
+    }
+
+    /// Records a single frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` isn't a valid event stream message.
+    pub fn record_frame(&mut self, frame: Bytes) {
+        let message = Message::read_from(&mut frame.clone()).unwrap_or_else(|err| {
+            panic!("frame {} is invalid: {}", self.frames.len(), err);
+        });
+        self.frames.push((message, frame));
+    }
+
+    /// The recorded frames, with the messages decoded from them
+    pub fn frames(&self) -> &[(Message, Bytes)] {
+        &self.frames
+    }
+
+    /// The number of recorded frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if no frames were recorded
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The event sent in the frame at `index`: the message wrapped by a signed frame, or the
+    /// frame's own message if it isn't signed. Returns `None` for the empty end frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no frame at `index`, or the payload of a signed frame isn't a message.
+    pub fn event(&self, index: usize) -> Option<Message> {
+        let message = self.message(index);
+        if !self.is_signed(message) {
+            return Some(message.clone());
+        }
+        if message.payload().is_empty() {
+            return None;
+        }
+        let event = Message::read_from(&mut &message.payload()[..]).unwrap_or_else(|err| {
+            panic!("frame {} doesn't wrap a message: {}", index, err);
+        });
+        Some(event)
+    }
+
+    /// The payload of the event sent in the frame at `index`, which is empty for the end frame
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`event`](FrameRecorder::event).
+    pub fn payload_of(&self, index: usize) -> Bytes {
+        self.event(index)
+            .map(|event| event.payload().clone())
+            .unwrap_or_default()
+    }
+
+    /// Asserts that every frame is signed
+    pub fn assert_signed(&self) {
+        for (index, (message, _)) in self.frames.iter().enumerate() {
+            assert!(
+                self.is_signed(message),
+                "frame {} has no `{}` header: {:?}",
+                index,
+                self.signature_header,
+                message.headers()
+            );
+        }
+    }
+
+    /// Asserts that the recorded frames carry events with the `:event-type`s in `expected`.
+    ///
+    /// The empty signed frame ending the stream has the event type `"end"`, and events without an
+    /// `:event-type` header have an empty one.
+    pub fn assert_event_types(&self, expected: &[&str]) {
+        let actual: Vec<String> = (0..self.frames.len())
+            .map(|index| self.event_type(index))
+            .collect();
+        assert_eq!(expected, &actual[..], "unexpected event types");
+    }
+
+    fn message(&self, index: usize) -> &Message {
+        match self.frames.get(index) {
+            Some((message, _)) => message,
+            None => panic!(
+                "no frame at index {}, only {} were recorded",
+                index,
+                self.frames.len()
+            ),
+        }
+    }
+
+    fn is_signed(&self, message: &Message) -> bool {
+        message
+            .headers()
+            .iter()
+            .any(|header| header.name().as_str() == self.signature_header)
+    }
+
+    fn event_type(&self, index: usize) -> String {
+        let event = match self.event(index) {
+            Some(event) => event,
+            None => return END.into(),
+        };
+        event
+            .headers()
+            .iter()
+            .find(|header| header.name().as_str() == EVENT_TYPE)
+            .and_then(|header| header.value().as_string().ok())
+            .map(|event_type| event_type.as_str().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameRecorder;
+    use crate::event_stream::MessageStreamAdapter;
+    use async_stream::stream;
+    use aws_smithy_eventstream::error::Error as EventStreamError;
+    use aws_smithy_eventstream::frame::{
+        Header, HeaderValue, MarshallMessage, Message, SignMessage, SignMessageError,
+    };
+
+    #[derive(Debug)]
+    struct TestError;
+    impl std::fmt::Display for TestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "TestError")
+        }
+    }
+    impl std::error::Error for TestError {}
+
+    /// Marshalls `(event type, payload)` pairs
+    #[derive(Debug)]
+    struct Marshaller;
+    impl MarshallMessage for Marshaller {
+        type Input = (&'static str, &'static str);
+
+        fn marshall(
+            &self,
+            (event_type, payload): Self::Input,
+        ) -> Result<Message, EventStreamError> {
+            Ok(Message::new(payload.as_bytes()).add_header(Header::new(
+                ":event-type",
+                HeaderValue::String(event_type.into()),
+            )))
+        }
+    }
+
+    /// Wraps messages like the SigV4 event stream signer, with a fake signature
+    #[derive(Debug)]
+    struct Signer;
+    impl SignMessage for Signer {
+        fn sign(&mut self, message: Message) -> Result<Message, SignMessageError> {
+            let mut buffer = Vec::new();
+            message.write_to(&mut buffer).unwrap();
+            Ok(Message::new(buffer).add_header(signature()))
+        }
+
+        fn sign_empty(&mut self) -> Option<Result<Message, SignMessageError>> {
+            Some(Ok(Message::new(&b""[..]).add_header(signature())))
+        }
+    }
+
+    fn signature() -> Header {
+        Header::new(
+            ":chunk-signature",
+            HeaderValue::ByteArray(vec![0; 32].into()),
+        )
+    }
+
+    #[tokio::test]
+    async fn frame_recorder() {
+        let adapter = MessageStreamAdapter::<_, TestError>::new(
+            Marshaller,
+            Signer,
+            Box::pin(stream! {
+                yield Ok(("initial-request", "{}"));
+                yield Ok(("Event", "hello"));
+            }),
+        );
+        let mut recorder = FrameRecorder::new();
+        recorder.record(adapter).await.unwrap();
+
+        assert_eq!(3, recorder.len());
+        recorder.assert_signed();
+        recorder.assert_event_types(&["initial-request", "Event", "end"]);
+        assert_eq!(b"hello", &recorder.payload_of(1)[..]);
+        assert!(recorder.payload_of(2).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "frame 0 has no `:chunk-signature` header")]
+    fn frame_recorder_unsigned_frame() {
+        let mut frame = Vec::new();
+        Message::new(&b"hello"[..]).write_to(&mut frame).unwrap();
+        let mut recorder = FrameRecorder::new();
+        recorder.record_frame(frame.into());
+        recorder.assert_signed();
+    }
+}
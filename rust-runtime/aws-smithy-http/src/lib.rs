@@ -14,6 +14,7 @@
 //! |----------------|-------------|
 //! | `rt-tokio`     | Provides features that are dependent on `tokio` including the `ByteStream::from_path` util |
 //! | `event-stream` | Provides Sender/Receiver implementations for Event Stream codegen. |
+//! | `test-util`    | Provides utilities for testing event streams |
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 